@@ -55,11 +55,13 @@ fn main() -> ! {
     let rcc = dp.RCC.constrain();
 
     let hse_freq = 8.MHz();
+    let ltdc_freq = 27_429.kHz();
     let clocks = rcc
         .cfgr
         .use_hse(hse_freq)
         .pclk2(32.MHz())
         .sysclk(180.MHz())
+        .ltdc_clk(ltdc_freq)
         .freeze();
     let mut delay = cp.SYST.delay(&clocks);
 
@@ -74,14 +76,13 @@ fn main() -> ! {
 
     // Initialize LTDC, needed to provide pixel clock to DSI
     defmt::info!("Initializing LTDC");
-    let ltdc_freq = 27_429.kHz();
     let _display = DisplayController::<u32>::new(
         dp.LTDC,
         dp.DMA2D,
         None,
         PixelFormat::ARGB8888,
         DISPLAY_CONFIGURATION,
-        Some(hse_freq),
+        &clocks,
     );
 
     // Initialize DSI Host
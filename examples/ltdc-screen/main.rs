@@ -67,12 +67,13 @@ fn main() -> ! {
 
     // HSE osc out in High Z
     gpioh.ph1.into_floating_input();
-    let _clocks = rcc_hal
+    let clocks = rcc_hal
         .cfgr
         .use_hse(25.MHz())
         .bypass_hse_oscillator()
         .sysclk(216.MHz())
         .hclk(216.MHz())
+        .ltdc_clk(screen::DISCO_SCREEN_CONFIG.pixel_clock())
         .freeze();
 
     // LCD enable: set it low first to avoid LCD bleed while setting up timings
@@ -83,7 +84,7 @@ fn main() -> ! {
     let mut backlight = gpiok.pk3.into_push_pull_output();
     backlight.set_high();
 
-    let mut display = screen::Stm32F7DiscoDisplay::new(perif.LTDC, perif.DMA2D, pins);
+    let mut display = screen::Stm32F7DiscoDisplay::new(perif.LTDC, perif.DMA2D, pins, &clocks);
     display
         .controller
         .config_layer(Layer::L1, unsafe { &mut FB_LAYER1 }, PixelFormat::RGB565);
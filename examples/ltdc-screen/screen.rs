@@ -6,7 +6,7 @@ use embedded_graphics::{
 use stm32f4xx_hal::{
     ltdc::{DisplayConfig, DisplayController, Layer, LtdcPins, PixelFormat, SupportedWord},
     pac::{DMA2D, LTDC},
-    prelude::*,
+    rcc::Clocks,
 };
 
 /// STM32F7-DISCO board display
@@ -31,14 +31,19 @@ pub struct Stm32F7DiscoDisplay<T: 'static + SupportedWord> {
 }
 
 impl<T: 'static + SupportedWord> Stm32F7DiscoDisplay<T> {
-    pub fn new(ltdc: LTDC, dma2d: DMA2D, pins: LtdcPins) -> Stm32F7DiscoDisplay<T> {
+    pub fn new(
+        ltdc: LTDC,
+        dma2d: DMA2D,
+        pins: LtdcPins,
+        clocks: &Clocks,
+    ) -> Stm32F7DiscoDisplay<T> {
         let controller = DisplayController::new(
             ltdc,
             dma2d,
             Some(pins),
             PixelFormat::RGB565,
             DISCO_SCREEN_CONFIG,
-            Some(25.MHz()),
+            clocks,
         );
 
         Stm32F7DiscoDisplay { controller }
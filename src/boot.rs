@@ -0,0 +1,179 @@
+//! Jumps to the system memory bootloader and to application images
+//!
+//! Before jumping, interrupts and SysTick are disabled, clocks are switched back to HSI and
+//! all peripherals are reset, so the target starts from a state close to a system reset:
+//!
+//! ```
+//! use stm32f4xx_hal::{boot, flash::LockedFlash, pac, prelude::*};
+//!
+//! let dp = pac::Peripherals::take().unwrap();
+//! let syscfg = dp.SYSCFG.constrain();
+//! let flash = LockedFlash::new(dp.FLASH);
+//! // Application linked at the second 128 KB sector
+//! match boot::Image::new(&flash, 128 * 1024) {
+//!     Ok(image) => unsafe { boot::jump_to_application(&image) },
+//!     Err(_) => boot::jump_to_bootloader(syscfg),
+//! }
+//! ```
+
+use cortex_m::peripheral::{NVIC, SCB, SYST};
+
+use crate::flash::FlashExt;
+use crate::pac::rcc::cfgr::SW;
+use crate::pac::RCC;
+use crate::syscfg::SysCfg;
+
+/// System memory holding the ST bootloader
+const SYSTEM_MEMORY: usize = 0x1fff_0000;
+/// `MEM_MODE` of `SYSCFG_MEMRMP` mapping system memory at 0
+const MEM_MODE_SYSTEM_MEMORY: u32 = 0b01;
+/// `VTOR` alignment for up to 128 vectors
+const VECTOR_TABLE_ALIGN: usize = 512;
+
+/// Image validation error
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Offset is outside of flash or not aligned for `VTOR`
+    InvalidAddress,
+    /// Initial stack pointer is not a word aligned address in SRAM or CCM RAM
+    InvalidStackPointer,
+    /// Reset vector is not a Thumb address in flash
+    InvalidResetVector,
+}
+
+/// Application image in flash starting with its vector table
+#[derive(Debug, Clone, Copy)]
+pub struct Image {
+    address: usize,
+}
+
+impl Image {
+    /// Validates the vector table at `offset` into flash
+    pub fn new(flash: &impl FlashExt, offset: usize) -> Result<Self, Error> {
+        if offset % VECTOR_TABLE_ALIGN != 0 || offset + 8 > flash.len() {
+            return Err(Error::InvalidAddress);
+        }
+        let image = Self {
+            address: flash.address() + offset,
+        };
+
+        let sp = image.stack_pointer();
+        if sp & 0b11 != 0 || !matches!(sp & 0xfff0_0000, 0x1000_0000 | 0x2000_0000) {
+            return Err(Error::InvalidStackPointer);
+        }
+        let flash_range = flash.address()..flash.address() + flash.len();
+        let reset = image.reset_vector();
+        if reset & 1 == 0 || !flash_range.contains(&(reset as usize & !1)) {
+            return Err(Error::InvalidResetVector);
+        }
+
+        Ok(image)
+    }
+
+    /// Memory-mapped address of the vector table
+    pub fn address(&self) -> usize {
+        self.address
+    }
+
+    /// Initial stack pointer, the first vector table entry
+    pub fn stack_pointer(&self) -> u32 {
+        unsafe { *(self.address as *const u32) }
+    }
+
+    /// Reset handler address, the second vector table entry
+    pub fn reset_vector(&self) -> u32 {
+        unsafe { *(self.address as *const u32).add(1) }
+    }
+}
+
+/// Jumps to the ST bootloader in system memory
+///
+/// System memory is mapped at address 0 so the bootloader finds its vector table there.
+pub fn jump_to_bootloader(syscfg: SysCfg) -> ! {
+    deinit();
+
+    // Peripheral reset cleared `SYSCFG_MEMRMP`, its clock is still enabled
+    syscfg
+        .memrmp()
+        .modify(|r, w| unsafe { w.bits((r.bits() & !0b11) | MEM_MODE_SYSTEM_MEMORY) });
+    jump(0, SYSTEM_MEMORY)
+}
+
+/// Jumps to an application image, relocating the vector table to it
+///
+/// # Safety
+///
+/// `image` must be a complete program for this device. Only its first two vectors are validated.
+pub unsafe fn jump_to_application(image: &Image) -> ! {
+    deinit();
+    jump(image.address as u32, image.address)
+}
+
+/// Brings the core and RCC close to their reset state
+fn deinit() {
+    cortex_m::interrupt::disable();
+
+    unsafe {
+        let syst = &*SYST::PTR;
+        syst.csr.write(0);
+        syst.rvr.write(0);
+        syst.cvr.write(0);
+
+        let nvic = &*NVIC::PTR;
+        for (icer, icpr) in nvic.icer.iter().zip(nvic.icpr.iter()) {
+            icer.write(!0);
+            icpr.write(!0);
+        }
+    }
+
+    let rcc = unsafe { &*RCC::ptr() };
+
+    // Run from HSI with all prescalers cleared
+    rcc.cr().modify(|_, w| w.hsion().set_bit());
+    while rcc.cr().read().hsirdy().bit_is_clear() {}
+    rcc.cfgr().write(|w| unsafe { w.bits(0) });
+    while rcc.cfgr().read().sws().bits() != SW::Hsi as u8 {}
+    rcc.cr().modify(|_, w| {
+        w.pllon().clear_bit();
+        #[cfg(not(feature = "gpio-f410"))]
+        w.plli2son().clear_bit();
+        #[cfg(feature = "sai")]
+        #[cfg(not(feature = "gpio-f413"))]
+        w.pllsaion().clear_bit();
+        w.csson().clear_bit();
+        w.hseon().clear_bit()
+    });
+    while rcc.cr().read().hserdy().bit_is_set() {}
+    rcc.cr().modify(|_, w| w.hsebyp().clear_bit());
+    // Disable clock interrupts and clear their flags
+    rcc.cir().write(|w| unsafe { w.bits(0x00ff_0000) });
+
+    // Reset every peripheral, clocks stay enabled
+    rcc.ahb1rstr().write(|w| unsafe { w.bits(!0) });
+    rcc.ahb1rstr().write(|w| unsafe { w.bits(0) });
+    #[cfg(not(feature = "gpio-f410"))]
+    {
+        rcc.ahb2rstr().write(|w| unsafe { w.bits(!0) });
+        rcc.ahb2rstr().write(|w| unsafe { w.bits(0) });
+    }
+    #[cfg(any(feature = "fsmc", feature = "fmc"))]
+    {
+        rcc.ahb3rstr().write(|w| unsafe { w.bits(!0) });
+        rcc.ahb3rstr().write(|w| unsafe { w.bits(0) });
+    }
+    rcc.apb1rstr().write(|w| unsafe { w.bits(!0) });
+    rcc.apb1rstr().write(|w| unsafe { w.bits(0) });
+    rcc.apb2rstr().write(|w| unsafe { w.bits(!0) });
+    rcc.apb2rstr().write(|w| unsafe { w.bits(0) });
+}
+
+/// Sets `VTOR` and starts the reset handler of `vector_table` with its stack pointer
+fn jump(vtor: u32, vector_table: usize) -> ! {
+    unsafe {
+        (*SCB::PTR).vtor.write(vtor);
+        // Targets expect interrupts enabled like after reset, all of them are disabled in NVIC
+        cortex_m::interrupt::enable();
+        cortex_m::asm::bootload(vector_table as *const u32)
+    }
+}
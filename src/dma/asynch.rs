@@ -0,0 +1,208 @@
+//! Awaiting DMA streams: [`Transfer::wait_async`] and helpers used by async drivers
+//!
+//! Every awaited stream needs its interrupt unmasked in the NVIC
+//! and [`StreamX::on_interrupt`] called from the handler:
+//!
+//! ```ignore
+//! #[interrupt]
+//! fn DMA2_STREAM3() {
+//!     dma::Stream3::<pac::DMA2>::on_interrupt();
+//! }
+//! ```
+
+use core::future::poll_fn;
+use core::sync::atomic::{compiler_fence, Ordering};
+use core::task::{Context, Poll};
+use enumflags2::BitFlags;
+
+use super::{
+    config, stream_disable,
+    traits::{Channel, DMASet, Direction, Instance, PeriAddress, Stream},
+    ChannelX, DmaChannel, DmaDataSize, DmaDirection, DmaEvent, DmaFlag, DmaFlowController, StreamX,
+    Transfer,
+};
+use crate::Listen;
+
+impl<I: Instance, const S: u8> StreamX<I, S>
+where
+    Self: Stream,
+{
+    /// Wakes the task awaiting this stream.
+    ///
+    /// Must be called from the `DMAx_STREAMy` interrupt handler.
+    /// Interrupts which were not enabled by the awaiting task are left enabled.
+    pub fn on_interrupt() {
+        let mut stream = Self::new();
+        // Mask interrupts until the woken task checks the status flags
+        let events = Self::waiter_events().swap(0, Ordering::Relaxed);
+        stream.unlisten(BitFlags::from_bits_truncate(events));
+        if events & FIFO_ERROR != 0 {
+            stream.unlisten_fifo_error();
+        }
+        Self::waker().wake();
+    }
+}
+
+/// FIFO error interrupt in [`Stream::waiter_events`], next to the [`DmaEvent`] bits
+const FIFO_ERROR: u32 = 1 << 31;
+
+/// Enables interrupts for the awaiting task, they are masked again by [`StreamX::on_interrupt`]
+fn listen_waiter<STREAM: Stream>(
+    stream: &mut STREAM,
+    events: BitFlags<DmaEvent>,
+    fifo_error: bool,
+) {
+    cortex_m::interrupt::free(|_| {
+        let mut bits = events.bits();
+        if fifo_error {
+            bits |= FIFO_ERROR;
+            stream.listen_fifo_error();
+        }
+        STREAM::waiter_events().fetch_or(bits, Ordering::Relaxed);
+        stream.listen(events);
+    });
+}
+
+/// Configures `stream` for a single transfer of `len` items and enables it.
+///
+/// `memory` is `(address, len)` of the memory side.
+///
+/// # Safety
+///
+/// Memory must stay valid until the transfer is completed or the stream is stopped.
+pub(crate) unsafe fn start_oneshot<STREAM: Stream>(
+    stream: &mut STREAM,
+    channel: DmaChannel,
+    direction: DmaDirection,
+    peripheral: u32,
+    memory: (u32, u16),
+    memory_increment: bool,
+    size: DmaDataSize,
+) {
+    stream_disable(stream);
+    stream.clear_all_flags();
+
+    stream.set_channel(channel);
+    stream.set_direction(direction);
+    stream.set_peripheral_address(peripheral);
+    stream.set_memory_address(memory.0);
+    stream.set_number_of_transfers(memory.1);
+    stream.set_memory_size(size);
+    stream.set_peripheral_size(size);
+    stream.set_memory_increment(memory_increment);
+    stream.set_peripheral_increment(false);
+    stream.set_circular_mode(false);
+    stream.set_double_buffer(false);
+    stream.set_fifo_enable(false);
+    stream.set_flow_controller(DmaFlowController::Dma);
+    stream.set_priority(config::Priority::Medium);
+    stream.unlisten_fifo_error();
+    let events = DmaEvent::TransferComplete | DmaEvent::TransferError | DmaEvent::DirectModeError;
+    STREAM::waiter_events().store(events.bits(), Ordering::Relaxed);
+    stream.listen_only(events);
+
+    // "Preceding reads and writes cannot be moved past subsequent writes"
+    compiler_fence(Ordering::Release);
+
+    stream.enable();
+}
+
+/// Disables `stream` and clears its flags.
+pub(crate) fn stop_stream<STREAM: Stream>(stream: &mut STREAM) {
+    stream.unlisten(BitFlags::ALL);
+    STREAM::waiter_events().store(0, Ordering::Relaxed);
+    stream_disable(stream);
+    stream.clear_all_flags();
+
+    // "Subsequent reads and writes cannot be moved ahead of preceding reads"
+    compiler_fence(Ordering::Acquire);
+}
+
+/// Polls the end of the transfer started on `stream`.
+///
+/// Returns the stream flags on transfer or direct mode error.
+pub(crate) fn poll_stream<STREAM: Stream>(
+    stream: &mut STREAM,
+    cx: &mut Context<'_>,
+) -> Poll<Result<(), BitFlags<DmaFlag>>> {
+    STREAM::waker().register(cx.waker());
+    let flags = stream.flags();
+    if flags.intersects(DmaFlag::TransferError | DmaFlag::DirectModeError) {
+        Poll::Ready(Err(flags))
+    } else if flags.contains(DmaFlag::TransferComplete) {
+        Poll::Ready(Ok(()))
+    } else {
+        listen_waiter(
+            stream,
+            DmaEvent::TransferComplete | DmaEvent::TransferError | DmaEvent::DirectModeError,
+            false,
+        );
+        Poll::Pending
+    }
+}
+
+impl<STREAM, const CHANNEL: u8, PERIPHERAL, DIR, BUF>
+    Transfer<STREAM, CHANNEL, PERIPHERAL, DIR, BUF>
+where
+    STREAM: Stream,
+    ChannelX<CHANNEL>: Channel,
+    DIR: Direction,
+    PERIPHERAL: PeriAddress + DMASet<STREAM, CHANNEL, DIR>,
+{
+    /// Waits until one of `done` flags or an error flag is set, `events` are listened meanwhile.
+    async fn wait_flags(
+        &mut self,
+        done: BitFlags<DmaFlag>,
+        events: BitFlags<DmaEvent>,
+    ) -> Result<(), BitFlags<DmaFlag>> {
+        let stream = &mut self.stream;
+        // FIFO error flag is meaningless in direct mode
+        let fifo = stream.is_fifo_enabled();
+        let mut errors = DmaFlag::TransferError | DmaFlag::DirectModeError;
+        if fifo {
+            errors |= DmaFlag::FifoError;
+        }
+        poll_fn(|cx| {
+            STREAM::waker().register(cx.waker());
+            let flags = stream.flags();
+            if flags.intersects(errors) {
+                Poll::Ready(Err(flags))
+            } else if flags.intersects(done) {
+                Poll::Ready(Ok(()))
+            } else {
+                listen_waiter(
+                    stream,
+                    events | DmaEvent::TransferError | DmaEvent::DirectModeError,
+                    fifo,
+                );
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Asynchronously waits for the transfer to complete.
+    ///
+    /// Stream interrupt must be unmasked in the NVIC and [`StreamX::on_interrupt`] called from its handler.
+    /// Returns the stream flags if transfer or direct mode error occurred, or FIFO error with FIFO
+    /// enabled. Flags are not cleared.
+    pub async fn wait_async(&mut self) -> Result<(), BitFlags<DmaFlag>> {
+        self.wait_flags(
+            DmaFlag::TransferComplete.into(),
+            DmaEvent::TransferComplete.into(),
+        )
+        .await
+    }
+
+    /// Asynchronously waits for the half or the full transfer to complete.
+    ///
+    /// Useful in circular or double buffer mode to process the inactive half of the data.
+    /// See [`wait_async`](Self::wait_async) for requirements and errors.
+    pub async fn wait_half_async(&mut self) -> Result<(), BitFlags<DmaFlag>> {
+        self.wait_flags(
+            DmaFlag::HalfTransfer | DmaFlag::TransferComplete,
+            DmaEvent::HalfTransfer | DmaEvent::TransferComplete,
+        )
+        .await
+    }
+}
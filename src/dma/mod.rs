@@ -20,8 +20,12 @@ use enumflags2::BitFlags;
 
 use crate::{pac, rcc};
 
+#[cfg(feature = "async")]
+mod asynch;
 pub mod traits;
 use crate::serial::RxISR;
+#[cfg(feature = "async")]
+pub(crate) use asynch::*;
 use traits::{
     sealed::Bits, Channel, DMASet, Direction, DmaEventExt, DmaFlagExt, Instance, PeriAddress,
     SafePeripheralRead, Stream, StreamISR,
@@ -641,6 +645,11 @@ where
             .modify(|_, w| w.dmdis().bit(fifo_enable));
     }
 
+    #[inline(always)]
+    fn is_fifo_enabled(&self) -> bool {
+        unsafe { Self::st() }.fcr().read().dmdis().bit_is_set()
+    }
+
     #[inline(always)]
     fn set_memory_burst(&mut self, memory_burst: config::BurstMode) {
         unsafe { Self::st() }
@@ -667,6 +676,18 @@ where
             CurrentBuffer::FirstBuffer
         }
     }
+
+    #[cfg(feature = "async")]
+    #[inline(always)]
+    fn waker() -> &'static atomic_waker::AtomicWaker {
+        &I::wakers()[S as usize]
+    }
+
+    #[cfg(feature = "async")]
+    #[inline(always)]
+    fn waiter_events() -> &'static core::sync::atomic::AtomicU32 {
+        &I::waiter_events()[S as usize]
+    }
 }
 
 impl<I: Instance, const S: u8> StreamX<I, S>
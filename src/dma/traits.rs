@@ -4,6 +4,8 @@ use crate::{
     timer,
 };
 use core::ops::Deref;
+#[cfg(feature = "async")]
+use core::sync::atomic::AtomicU32;
 use enumflags2::BitFlags;
 
 pub(crate) mod sealed {
@@ -245,6 +247,9 @@ pub trait Stream: StreamISR + crate::Listen<Event = DmaEvent> + crate::Sealed {
     /// Enable/disable the fifo (dmdis) of the DMA stream.
     fn set_fifo_enable(&mut self, fifo_enable: bool);
 
+    /// Returns `true` if the fifo is enabled, `false` in direct mode.
+    fn is_fifo_enabled(&self) -> bool;
+
     /// Set memory burst mode (mburst) of the DMA stream.
     fn set_memory_burst(&mut self, memory_burst: config::BurstMode);
 
@@ -256,6 +261,15 @@ pub trait Stream: StreamISR + crate::Listen<Event = DmaEvent> + crate::Sealed {
 
     /// Get which buffer is currently in use by the DMA.
     fn current_buffer(&self) -> CurrentBuffer;
+
+    #[cfg(feature = "async")]
+    #[doc(hidden)]
+    fn waker() -> &'static atomic_waker::AtomicWaker;
+
+    /// Interrupts enabled by the task awaiting the stream
+    #[cfg(feature = "async")]
+    #[doc(hidden)]
+    fn waiter_events() -> &'static AtomicU32;
 }
 
 /// DMA direction.
@@ -305,10 +319,40 @@ pub type DMARegisterBlock = pac::dma1::RegisterBlock;
 pub trait Instance:
     crate::Sealed + crate::Ptr<RB = DMARegisterBlock> + Deref<Target = Self::RB>
 {
+    #[cfg(feature = "async")]
+    #[doc(hidden)]
+    fn wakers() -> &'static [atomic_waker::AtomicWaker; 8];
+
+    #[cfg(feature = "async")]
+    #[doc(hidden)]
+    fn waiter_events() -> &'static [AtomicU32; 8];
+}
+
+macro_rules! dma_instance {
+    ($($DMA:ty),+) => {
+        $(
+            impl Instance for $DMA {
+                #[cfg(feature = "async")]
+                fn wakers() -> &'static [atomic_waker::AtomicWaker; 8] {
+                    #[allow(clippy::declare_interior_mutable_const)]
+                    const NEW: atomic_waker::AtomicWaker = atomic_waker::AtomicWaker::new();
+                    static WAKERS: [atomic_waker::AtomicWaker; 8] = [NEW; 8];
+                    &WAKERS
+                }
+
+                #[cfg(feature = "async")]
+                fn waiter_events() -> &'static [AtomicU32; 8] {
+                    #[allow(clippy::declare_interior_mutable_const)]
+                    const NEW: AtomicU32 = AtomicU32::new(0);
+                    static EVENTS: [AtomicU32; 8] = [NEW; 8];
+                    &EVENTS
+                }
+            }
+        )+
+    };
 }
 
-impl Instance for DMA1 {}
-impl Instance for DMA2 {}
+dma_instance!(DMA1, DMA2);
 
 /// A trait for marker tha represent Channel of a DMA stream.
 pub trait Channel {
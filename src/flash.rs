@@ -4,17 +4,45 @@ use embedded_storage::nor_flash::{
 
 use crate::pac::flash::cr::PSIZE;
 use crate::pac::FLASH;
+use crate::rcc::VoltageRange;
 use crate::signature::FlashSize;
 use core::{ptr, slice};
 
+#[cfg(feature = "async")]
+mod asynch;
+#[cfg(any(feature = "gpio-f427", feature = "gpio-f469"))]
+mod dual_bank;
+pub mod eeprom;
+mod option_bytes;
+mod otp;
+#[cfg(feature = "async")]
+pub use asynch::{on_interrupt, AsyncFlash};
+#[cfg(any(feature = "gpio-f427", feature = "gpio-f469"))]
+pub use dual_bank::{active_bank, Bank};
+#[cfg(any(feature = "gpio-f427", feature = "gpio-f446", feature = "gpio-f469"))]
+pub use option_bytes::ProtectionMode;
+pub use option_bytes::{BorLevel, OptionBytes, ReadProtection, UnlockedOptionBytes};
+pub use otp::{OTP_BLOCKS, OTP_BLOCK_SIZE};
+
 /// Flash erase/program error
 #[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
 pub enum Error {
     ProgrammingSequence,
     ProgrammingParallelism,
     ProgrammingAlignment,
     WriteProtection,
     Operation,
+    /// OTP block is locked
+    OtpLocked,
+    /// Programmed data differs
+    Verification,
+    /// Read-out protection level 2 requested from [`UnlockedOptionBytes::program`]
+    ReadProtectionLevel2,
+    /// Erase of the bank the system runs from
+    ActiveBank,
+    /// Offset and length exceed the flash bank
+    OutOfRange,
 }
 
 impl Error {
@@ -36,6 +64,54 @@ impl Error {
     }
 }
 
+/// Erase/program parallelism
+///
+/// Wider parallelism is faster but needs a higher supply voltage.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Parallelism {
+    /// Byte access, any supply voltage
+    #[default]
+    X8,
+    /// Half-word access, 2.1 V and above
+    X16,
+    /// Word access, 2.7 V and above
+    X32,
+    /// Double word access, requires external VPP
+    X64,
+}
+
+impl Parallelism {
+    fn psize(self) -> PSIZE {
+        match self {
+            Self::X8 => PSIZE::Psize8,
+            Self::X16 => PSIZE::Psize16,
+            Self::X32 => PSIZE::Psize32,
+            Self::X64 => PSIZE::Psize64,
+        }
+    }
+
+    fn bytes(self) -> usize {
+        match self {
+            Self::X8 => 1,
+            Self::X16 => 2,
+            Self::X32 => 4,
+            Self::X64 => 8,
+        }
+    }
+}
+
+impl From<VoltageRange> for Parallelism {
+    /// Widest parallelism without external VPP
+    fn from(range: VoltageRange) -> Self {
+        match range {
+            VoltageRange::V1_8To2_1 => Self::X8,
+            VoltageRange::V2_1To2_4 | VoltageRange::V2_4To2_7 => Self::X16,
+            VoltageRange::V2_7To3_6 => Self::X32,
+        }
+    }
+}
+
 /// Flash methods implemented for `pac::FLASH`
 #[allow(clippy::len_without_is_empty)]
 pub trait FlashExt {
@@ -55,6 +131,19 @@ pub trait FlashExt {
     fn dual_bank(&self) -> bool;
     /// Returns flash memory sector of a given offset. Returns none if offset is out of range.
     fn sector(&self, offset: usize) -> Option<FlashSector>;
+    /// Reads the option bytes
+    fn option_bytes(&self) -> OptionBytes;
+    /// Unlock option bytes for programming until this method's
+    /// result is dropped
+    fn unlocked_options(&mut self) -> UnlockedOptionBytes;
+    /// Returns a copy of an OTP block, panics if `block` is out of range
+    fn read_otp(&self, block: u8) -> [u8; OTP_BLOCK_SIZE] {
+        otp::read(block)
+    }
+    /// Returns true if an OTP block is locked, panics if `block` is out of range
+    fn otp_locked(&self, block: u8) -> bool {
+        otp::is_locked(block)
+    }
 }
 
 impl FlashExt for FLASH {
@@ -68,7 +157,10 @@ impl FlashExt for FLASH {
 
     fn unlocked(&mut self) -> UnlockedFlash {
         unlock(self);
-        UnlockedFlash { flash: self }
+        UnlockedFlash {
+            flash: self,
+            parallelism: Parallelism::X8,
+        }
     }
 
     fn dual_bank(&self) -> bool {
@@ -100,6 +192,14 @@ impl FlashExt for FLASH {
     fn sector(&self, offset: usize) -> Option<FlashSector> {
         flash_sectors(self.len(), self.dual_bank()).find(|s| s.contains(offset))
     }
+
+    fn option_bytes(&self) -> OptionBytes {
+        option_bytes::read(self)
+    }
+
+    fn unlocked_options(&mut self) -> UnlockedOptionBytes {
+        UnlockedOptionBytes::new(self)
+    }
 }
 
 /// Read-only flash
@@ -149,6 +249,14 @@ impl FlashExt for LockedFlash {
     fn sector(&self, offset: usize) -> Option<FlashSector> {
         self.flash.sector(offset)
     }
+
+    fn option_bytes(&self) -> OptionBytes {
+        self.flash.option_bytes()
+    }
+
+    fn unlocked_options(&mut self) -> UnlockedOptionBytes {
+        self.flash.unlocked_options()
+    }
 }
 
 /// Result of `FlashExt::unlocked()`
@@ -157,15 +265,20 @@ impl FlashExt for LockedFlash {
 ///
 /// ```
 /// use stm32f4xx_hal::pac::Peripherals;
+/// use stm32f4xx_hal::prelude::*;
 /// use stm32f4xx_hal::flash::{FlashExt, LockedFlash, UnlockedFlash};
 /// use embedded_storage::nor_flash::NorFlash;
 ///
 /// let dp = Peripherals::take().unwrap();
+/// let clocks = dp.RCC.constrain().cfgr.freeze();
 /// let mut flash = LockedFlash::new(dp.FLASH);
 ///
 /// // Unlock flash for writing
 /// let mut unlocked_flash = flash.unlocked();
 ///
+/// // Use word access at 3.3 V
+/// unlocked_flash.set_parallelism(clocks.voltage_range().into());
+///
 /// // Erase the second 128 KB sector.
 /// NorFlash::erase(&mut unlocked_flash, 128 * 1024, 256 * 1024).unwrap();
 ///
@@ -178,6 +291,7 @@ impl FlashExt for LockedFlash {
 /// ```
 pub struct UnlockedFlash<'a> {
     flash: &'a mut FLASH,
+    parallelism: Parallelism,
 }
 
 /// Automatically lock flash erase/program when leaving scope
@@ -188,17 +302,59 @@ impl Drop for UnlockedFlash<'_> {
 }
 
 impl UnlockedFlash<'_> {
+    /// Sets the parallelism of erase and program operations, [`Parallelism::X8`] by default
+    pub fn set_parallelism(&mut self, parallelism: Parallelism) {
+        self.parallelism = parallelism;
+    }
+
+    /// Enables the end of operation and operation error interrupts
+    pub fn listen(&mut self) {
+        self.flash.cr().modify(|_, w| {
+            w.eopie().set_bit();
+            w.errie().set_bit()
+        });
+    }
+
+    /// Disables the end of operation and operation error interrupts
+    pub fn unlisten(&mut self) {
+        self.flash.cr().modify(|_, w| {
+            w.eopie().clear_bit();
+            w.errie().clear_bit()
+        });
+    }
+
+    /// Returns `WouldBlock` while an erase or program operation is ongoing, then its result
+    ///
+    /// Clears the end of operation and error flags, so it can be called from the `FLASH`
+    /// interrupt handler.
+    pub fn poll(&mut self) -> nb::Result<(), Error> {
+        if self.flash.sr().read().bsy().bit() {
+            return Err(nb::Error::WouldBlock);
+        }
+        self.flash.cr().modify(|_, w| w.pg().clear_bit());
+        let result = self.ok();
+        self.flash.sr().write(|w| unsafe { w.bits(SR_FLAGS) });
+        result.map_err(nb::Error::Other)
+    }
+
     /// Erase a flash sector
     ///
     /// Refer to the reference manual to see which sector corresponds
     /// to which memory address.
     pub fn erase(&mut self, sector: u8) -> Result<(), Error> {
+        self.start_erase(sector);
+        self.wait_ready();
+        self.ok()
+    }
+
+    /// Starts erasing a flash sector without waiting, see [`UnlockedFlash::poll`]
+    pub fn start_erase(&mut self, sector: u8) {
         let snb = if sector < 12 { sector } else { sector + 4 };
 
         self.flash.cr().modify(|_, w| {
             // start
             w.strt().set_bit();
-            w.psize().variant(PSIZE::Psize8);
+            w.psize().variant(self.parallelism.psize());
             // sector number
             unsafe {
                 w.snb().bits(snb);
@@ -208,49 +364,123 @@ impl UnlockedFlash<'_> {
             // no programming
             w.pg().clear_bit()
         });
-        self.wait_ready();
-        self.ok()
     }
 
-    /// Program bytes with offset into flash memory,
-    /// aligned to 128-bit rows
-    pub fn program<'a, I>(&mut self, mut offset: usize, mut bytes: I) -> Result<(), Error>
+    /// Starts programming 1, 2, 4 or 8 bytes at an aligned offset without waiting,
+    /// see [`UnlockedFlash::poll`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if `offset` is not a multiple of the length of `word`.
+    pub fn start_program(&mut self, offset: usize, word: &[u8]) {
+        assert!(matches!(word.len(), 1 | 2 | 4 | 8) && offset % word.len() == 0);
+        let dst = unsafe { (self.flash.address() as *mut u8).add(offset) };
+        self.start_program_word(dst, word);
+    }
+
+    /// Program bytes with offset into flash memory
+    ///
+    /// Aligned words are programmed with the selected parallelism, the rest byte by byte.
+    pub fn program<'a, I>(&mut self, offset: usize, bytes: I) -> Result<(), Error>
     where
         I: Iterator<Item = &'a u8>,
     {
-        let ptr = self.flash.address() as *mut u8;
-        let mut bytes_written = 1;
-        while bytes_written > 0 {
-            bytes_written = 0;
-            let amount = 16 - (offset % 16);
-
-            self.flash.cr().modify(|_, w| {
-                w.psize().variant(PSIZE::Psize8);
-                // no sector erase
-                w.ser().clear_bit();
-                // programming
-                w.pg().set_bit()
-            });
-            for _ in 0..amount {
+        self.program_at(self.flash.address() as *mut u8, offset, bytes)
+    }
+
+    /// Programs bytes with offset from a memory-mapped address
+    fn program_at<'a, I>(
+        &mut self,
+        ptr: *mut u8,
+        mut offset: usize,
+        mut bytes: I,
+    ) -> Result<(), Error>
+    where
+        I: Iterator<Item = &'a u8>,
+    {
+        let width = self.parallelism.bytes();
+        let mut word = [0; 8];
+        loop {
+            let amount = if offset % width == 0 { width } else { 1 };
+            let mut len = 0;
+            while len < amount {
                 match bytes.next() {
                     Some(byte) => {
-                        unsafe {
-                            ptr::write_volatile(ptr.add(offset), *byte);
-                        }
-                        offset += 1;
-                        bytes_written += 1;
+                        word[len] = *byte;
+                        len += 1;
                     }
                     None => break,
                 }
             }
-            self.wait_ready();
-            self.ok()?;
+            if len == 0 {
+                break;
+            }
+
+            let result = if len == width {
+                self.program_word(unsafe { ptr.add(offset) }, &word[..len])
+            } else {
+                word[..len].iter().enumerate().try_for_each(|(i, b)| {
+                    self.program_word(unsafe { ptr.add(offset + i) }, slice::from_ref(b))
+                })
+            };
+            if result.is_err() {
+                self.flash.cr().modify(|_, w| w.pg().clear_bit());
+                return result;
+            }
+            offset += len;
         }
         self.flash.cr().modify(|_, w| w.pg().clear_bit());
 
         Ok(())
     }
 
+    /// Programs 1, 2, 4 or 8 bytes at an aligned address
+    fn program_word(&mut self, dst: *mut u8, word: &[u8]) -> Result<(), Error> {
+        self.start_program_word(dst, word);
+        self.wait_ready();
+        self.ok()
+    }
+
+    fn start_program_word(&mut self, dst: *mut u8, word: &[u8]) {
+        let parallelism = match word.len() {
+            1 => Parallelism::X8,
+            2 => Parallelism::X16,
+            4 => Parallelism::X32,
+            _ => Parallelism::X64,
+        };
+        self.flash.cr().modify(|_, w| {
+            w.psize().variant(parallelism.psize());
+            // no sector erase
+            w.ser().clear_bit();
+            // programming
+            w.pg().set_bit()
+        });
+        unsafe {
+            match parallelism {
+                Parallelism::X8 => ptr::write_volatile(dst, word[0]),
+                Parallelism::X16 => {
+                    ptr::write_volatile(dst as *mut u16, u16::from_le_bytes([word[0], word[1]]))
+                }
+                Parallelism::X32 => ptr::write_volatile(
+                    dst as *mut u32,
+                    u32::from_le_bytes([word[0], word[1], word[2], word[3]]),
+                ),
+                Parallelism::X64 => {
+                    // Double word is written as two consecutive words
+                    let dst = dst as *mut u32;
+                    ptr::write_volatile(
+                        dst,
+                        u32::from_le_bytes([word[0], word[1], word[2], word[3]]),
+                    );
+                    ptr::write_volatile(
+                        dst.add(1),
+                        u32::from_le_bytes([word[4], word[5], word[6], word[7]]),
+                    );
+                }
+            }
+        }
+    }
+
     fn ok(&self) -> Result<(), Error> {
         Error::read(self.flash).map(Err).unwrap_or(Ok(()))
     }
@@ -260,6 +490,9 @@ impl UnlockedFlash<'_> {
     }
 }
 
+/// `EOP`, `OPERR`, `WRPERR`, `PGAERR`, `PGPERR` and `PGSERR` flags
+const SR_FLAGS: u32 = 0xf3;
+
 const UNLOCK_KEY1: u32 = 0x45670123;
 const UNLOCK_KEY2: u32 = 0xCDEF89AB;
 
@@ -0,0 +1,146 @@
+//! Erasing and programming flash with [`embedded_storage_async::nor_flash::NorFlash`]
+//!
+//! Flash reads stall while an operation is ongoing, so other tasks only make progress when
+//! running from RAM, from the ART accelerator cache or from the other bank of a dual bank
+//! device.
+//!
+//! The `FLASH` interrupt must be unmasked in the NVIC and [`on_interrupt`] called from its
+//! handler:
+//!
+//! ```ignore
+//! #[interrupt]
+//! fn FLASH() {
+//!     flash::on_interrupt();
+//! }
+//! ```
+
+use core::future::poll_fn;
+use core::task::Poll;
+
+use atomic_waker::AtomicWaker;
+use embedded_storage_async::nor_flash::{ErrorType, MultiwriteNorFlash, NorFlash, ReadNorFlash};
+
+use super::{flash_sectors, Error, FlashExt, UnlockedFlash};
+use crate::pac::FLASH;
+
+static WAKER: AtomicWaker = AtomicWaker::new();
+
+/// Wakes the task waiting for an erase or program operation.
+///
+/// Must be called from the `FLASH` interrupt handler when [`AsyncFlash`] is used.
+pub fn on_interrupt() {
+    let flash = unsafe { &*FLASH::ptr() };
+    // Disable interrupts until the woken task starts the next operation
+    cortex_m::interrupt::free(|_| {
+        flash.cr().modify(|_, w| {
+            w.eopie().clear_bit();
+            w.errie().clear_bit()
+        });
+    });
+    WAKER.wake();
+}
+
+/// Unlocked flash waiting for erase and program operations asynchronously
+pub struct AsyncFlash<'a> {
+    flash: UnlockedFlash<'a>,
+}
+
+impl<'a> AsyncFlash<'a> {
+    pub fn new(flash: UnlockedFlash<'a>) -> Self {
+        Self { flash }
+    }
+
+    /// Releases the unlocked flash
+    pub fn release(self) -> UnlockedFlash<'a> {
+        self.flash
+    }
+
+    /// Erase a flash sector
+    pub async fn erase_sector(&mut self, sector: u8) -> Result<(), Error> {
+        self.flash.listen();
+        self.flash.start_erase(sector);
+        self.wait().await
+    }
+
+    /// Program bytes with offset into flash memory
+    ///
+    /// Aligned words are programmed with the selected parallelism, the rest byte by byte.
+    pub async fn program(&mut self, mut offset: usize, mut bytes: &[u8]) -> Result<(), Error> {
+        let width = self.flash.parallelism.bytes();
+        while !bytes.is_empty() {
+            let len = if offset % width == 0 && bytes.len() >= width {
+                width
+            } else {
+                1
+            };
+            self.flash.listen();
+            self.flash.start_program(offset, &bytes[..len]);
+            self.wait().await?;
+            offset += len;
+            bytes = &bytes[len..];
+        }
+        Ok(())
+    }
+
+    async fn wait(&mut self) -> Result<(), Error> {
+        poll_fn(|cx| {
+            WAKER.register(cx.waker());
+            match self.flash.poll() {
+                Ok(()) => Poll::Ready(Ok(())),
+                Err(nb::Error::Other(e)) => Poll::Ready(Err(e)),
+                Err(nb::Error::WouldBlock) => Poll::Pending,
+            }
+        })
+        .await
+    }
+}
+
+impl ErrorType for AsyncFlash<'_> {
+    type Error = Error;
+}
+
+impl ReadNorFlash for AsyncFlash<'_> {
+    const READ_SIZE: usize = 1;
+
+    async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        let offset = offset as usize;
+        bytes.copy_from_slice(&self.flash.flash.read()[offset..offset + bytes.len()]);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.flash.flash.len()
+    }
+}
+
+impl NorFlash for AsyncFlash<'_> {
+    const WRITE_SIZE: usize = 1;
+
+    // Use largest sector size of 128 KB. All smaller sectors will be erased together.
+    const ERASE_SIZE: usize = 128 * 1024;
+
+    async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        let mut current = from as usize;
+
+        let flash = &self.flash.flash;
+        for sector in flash_sectors(flash.len(), flash.dual_bank()) {
+            if sector.contains(current) {
+                self.erase_sector(sector.number).await?;
+                current += sector.size;
+            }
+
+            if current >= to as usize {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.program(offset as usize, bytes).await
+    }
+}
+
+// STM32F4 supports multiple writes
+impl MultiwriteNorFlash for AsyncFlash<'_> {}
@@ -0,0 +1,148 @@
+//! Dual bank firmware update
+//!
+//! On dual bank devices the bank the system booted from is mapped at the start of flash and
+//! the other one at half of the flash size. A new image is written to the inactive bank while
+//! running from the active one, then `BFB2` selects it for the next boot:
+//!
+//! ```
+//! use stm32f4xx_hal::{
+//!     flash::{self, FlashExt, LockedFlash},
+//!     pac,
+//!     prelude::*,
+//! };
+//!
+//! let dp = pac::Peripherals::take().unwrap();
+//! let image: &[u8] = &[0; 1024];
+//! let syscfg = dp.SYSCFG.constrain();
+//! let mut flash = LockedFlash::new(dp.FLASH);
+//! let active = flash::active_bank(&syscfg);
+//!
+//! let mut unlocked = flash.unlocked();
+//! unlocked.erase_bank(active.other(), &syscfg).unwrap();
+//! unlocked.program_inactive_bank(0, image.iter()).unwrap();
+//! unlocked.verify_inactive_bank(0, image).unwrap();
+//! drop(unlocked);
+//!
+//! let mut options = flash.unlocked_options();
+//! options.boot_from(active.other()).unwrap();
+//! options.reload();
+//! ```
+
+use super::{Error, FlashExt, UnlockedFlash, UnlockedOptionBytes};
+use crate::syscfg::SysCfg;
+
+const CR_MER: u32 = 1 << 2;
+const CR_MER2: u32 = 1 << 15;
+const CR_STRT: u32 = 1 << 16;
+const MEMRMP_UFB_MODE: u32 = 1 << 8;
+
+/// Physical flash bank
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bank {
+    Bank1,
+    Bank2,
+}
+
+impl Bank {
+    /// Returns the other bank
+    pub fn other(self) -> Self {
+        match self {
+            Self::Bank1 => Self::Bank2,
+            Self::Bank2 => Self::Bank1,
+        }
+    }
+}
+
+/// Returns the bank mapped at the start of flash, the one the system booted from
+pub fn active_bank(syscfg: &SysCfg) -> Bank {
+    if syscfg.memrmp().read().bits() & MEMRMP_UFB_MODE != 0 {
+        Bank::Bank2
+    } else {
+        Bank::Bank1
+    }
+}
+
+impl UnlockedFlash<'_> {
+    /// Erases a whole bank
+    ///
+    /// Returns [`Error::ActiveBank`] if `bank` is the one the system runs from.
+    ///
+    /// # Panics
+    ///
+    /// Panics if flash is not in dual bank organization.
+    pub fn erase_bank(&mut self, bank: Bank, syscfg: &SysCfg) -> Result<(), Error> {
+        assert!(self.flash.dual_bank());
+        if bank == active_bank(syscfg) {
+            return Err(Error::ActiveBank);
+        }
+        let mer = match bank {
+            Bank::Bank1 => CR_MER,
+            Bank::Bank2 => CR_MER2,
+        };
+
+        self.flash.cr().modify(|_, w| {
+            w.psize().variant(self.parallelism.psize());
+            // no sector erase
+            w.ser().clear_bit();
+            // no programming
+            w.pg().clear_bit()
+        });
+        self.flash
+            .cr()
+            .modify(|r, w| unsafe { w.bits(r.bits() | mer | CR_STRT) });
+        self.wait_ready();
+        self.flash
+            .cr()
+            .modify(|r, w| unsafe { w.bits(r.bits() & !(CR_MER | CR_MER2)) });
+        self.ok()
+    }
+
+    /// Programs bytes with offset into the bank mapped at half of the flash size
+    ///
+    /// Returns [`Error::OutOfRange`] if the bytes do not fit into the bank.
+    ///
+    /// # Panics
+    ///
+    /// Panics if flash is not in dual bank organization.
+    pub fn program_inactive_bank<'a, I>(&mut self, offset: usize, bytes: I) -> Result<(), Error>
+    where
+        I: ExactSizeIterator<Item = &'a u8>,
+    {
+        assert!(self.flash.dual_bank());
+        let start = self.inactive_bank_offset(offset, bytes.len())?;
+        self.program(start, bytes)
+    }
+
+    /// Compares bytes with offset into the bank mapped at half of the flash size
+    ///
+    /// Returns [`Error::OutOfRange`] if the bytes do not fit into the bank.
+    pub fn verify_inactive_bank(&self, offset: usize, bytes: &[u8]) -> Result<(), Error> {
+        let start = self.inactive_bank_offset(offset, bytes.len())?;
+        if self.flash.read()[start..start + bytes.len()] == *bytes {
+            Ok(())
+        } else {
+            Err(Error::Verification)
+        }
+    }
+
+    /// Returns the flash offset of `offset` into the inactive bank if `len` bytes fit
+    fn inactive_bank_offset(&self, offset: usize, len: usize) -> Result<usize, Error> {
+        let bank_size = self.flash.len() / 2;
+        match offset.checked_add(len) {
+            Some(end) if end <= bank_size => Ok(bank_size + offset),
+            _ => Err(Error::OutOfRange),
+        }
+    }
+}
+
+impl UnlockedOptionBytes<'_> {
+    /// Selects the bank to boot from with `BFB2`, taking effect after reset
+    ///
+    /// Boot falls back to bank 1 when bank 2 does not contain a valid stack pointer.
+    pub fn boot_from(&mut self, bank: Bank) -> Result<(), Error> {
+        let mut ob = self.read();
+        ob.boot_bank2 = bank == Bank::Bank2;
+        self.program(&ob)
+    }
+}
@@ -0,0 +1,354 @@
+//! EEPROM emulation
+//!
+//! Emulates a small byte-addressed EEPROM on two or more reserved flash sectors. Only the
+//! sector holding the valid page is written to: every changed 32-bit word is appended as a
+//! CRC protected record, reads return the newest record of each word. When the page is full,
+//! the newest value of every word is transferred to the next sector, which becomes valid
+//! before the old sector is erased.
+//!
+//! Page headers only ever clear bits, so an interrupted write, transfer or erase is detected
+//! and recovered by [`Eeprom::new`]:
+//!
+//! ```
+//! use stm32f4xx_hal::{
+//!     crc32::Crc32,
+//!     flash::{eeprom::Eeprom, LockedFlash, Parallelism},
+//!     pac,
+//! };
+//!
+//! let dp = pac::Peripherals::take().unwrap();
+//! let crc = Crc32::new(dp.CRC);
+//! let flash = LockedFlash::new(dp.FLASH);
+//! // Two 16 KB sectors holding 256 bytes
+//! let mut eeprom = Eeprom::new(flash, crc, [1, 2], 256, Parallelism::X32).unwrap();
+//!
+//! let mut boots = [0; 4];
+//! eeprom.read(0, &mut boots).unwrap();
+//! let boots = u32::from_le_bytes(boots).wrapping_add(1);
+//! eeprom.write(0, &boots.to_le_bytes()).unwrap();
+//! ```
+
+use core::slice;
+
+use embedded_storage::{ReadStorage, Storage};
+
+use super::{flash_sectors, FlashExt, FlashSector, Parallelism, UnlockedFlash};
+use crate::crc32::Crc32;
+
+/// Page header states, erased words read as `ERASED` too
+const ERASED: u32 = 0xffff_ffff;
+const RECEIVING: u32 = 0xffff_0000;
+const VALID: u32 = 0x0000_0000;
+
+/// State and sequence number words
+const HEADER_SIZE: usize = 8;
+/// Index, data and CRC words
+const RECORD_SIZE: usize = 12;
+
+/// EEPROM emulation error
+#[derive(Debug, Clone, Copy)]
+pub enum Error {
+    /// Flash erase/program failed
+    Flash(super::Error),
+    /// Accessed range is outside of the emulated EEPROM
+    OutOfBounds,
+}
+
+impl From<super::Error> for Error {
+    fn from(e: super::Error) -> Self {
+        Self::Flash(e)
+    }
+}
+
+/// Emulated EEPROM on `N` flash sectors
+pub struct Eeprom<F, const N: usize> {
+    flash: F,
+    pages: Pages<N>,
+}
+
+impl<F: FlashExt, const N: usize> Eeprom<F, N> {
+    /// Creates an emulated EEPROM of `capacity` bytes on the given flash sectors.
+    ///
+    /// The sectors must not be used for anything else. Interrupted page transfers are rolled
+    /// back, sectors without a valid page are formatted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if fewer than 2 sectors are given, a sector does not exist or the smallest sector
+    /// can not hold a record of every word.
+    pub fn new(
+        mut flash: F,
+        crc: Crc32,
+        sectors: [u8; N],
+        capacity: usize,
+        parallelism: Parallelism,
+    ) -> Result<Self, Error> {
+        assert!(N >= 2);
+        let (len, dual_bank) = (flash.len(), flash.dual_bank());
+        let sectors = sectors.map(|number| {
+            flash_sectors(len, dual_bank)
+                .find(|s| s.number == number)
+                .expect("sector does not exist")
+        });
+        let words = (capacity + 3) / 4;
+        assert!(sectors
+            .iter()
+            .all(|s| HEADER_SIZE + (words + 1) * RECORD_SIZE <= s.size));
+
+        let mut pages = Pages {
+            crc,
+            base: flash.address(),
+            sectors,
+            parallelism,
+            capacity,
+            words,
+            active: 0,
+            next: HEADER_SIZE,
+        };
+        {
+            let mut unlocked = pages.unlocked(&mut flash);
+            pages.mount(&mut unlocked)?;
+        }
+        Ok(Self { flash, pages })
+    }
+
+    /// Releases the flash and the CRC unit
+    pub fn release(self) -> (F, Crc32) {
+        (self.flash, self.pages.crc)
+    }
+
+    fn range(&self, offset: u32, len: usize) -> Result<core::ops::Range<usize>, Error> {
+        let start = offset as usize;
+        match start.checked_add(len) {
+            Some(end) if end <= self.pages.capacity => Ok(start..end),
+            _ => Err(Error::OutOfBounds),
+        }
+    }
+}
+
+impl<F: FlashExt, const N: usize> ReadStorage for Eeprom<F, N> {
+    type Error = Error;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        let range = self.range(offset, bytes.len())?;
+        for (pos, byte) in range.clone().zip(bytes.iter_mut()) {
+            *byte = self.pages.word(pos / 4).to_le_bytes()[pos % 4];
+        }
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.pages.capacity
+    }
+}
+
+impl<F: FlashExt, const N: usize> Storage for Eeprom<F, N> {
+    /// Appends a record for every changed word, transferring the page when it is full
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        let range = self.range(offset, bytes.len())?;
+        let mut flash = self.pages.unlocked(&mut self.flash);
+        for index in range.start / 4..(range.end + 3) / 4 {
+            let old = self.pages.word(index);
+            let mut data = old.to_le_bytes();
+            for (i, byte) in data.iter_mut().enumerate() {
+                let pos = index * 4 + i;
+                if range.contains(&pos) {
+                    *byte = bytes[pos - range.start];
+                }
+            }
+            let data = u32::from_le_bytes(data);
+            if data != old {
+                self.pages.append(&mut flash, index, data)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sectors holding the pages
+struct Pages<const N: usize> {
+    crc: Crc32,
+    base: usize,
+    sectors: [FlashSector; N],
+    parallelism: Parallelism,
+    capacity: usize,
+    words: usize,
+    /// Index of the sector holding the valid page
+    active: usize,
+    /// Offset of the first free record in the valid page
+    next: usize,
+}
+
+impl<const N: usize> Pages<N> {
+    fn unlocked<'a>(&self, flash: &'a mut impl FlashExt) -> UnlockedFlash<'a> {
+        let mut flash = flash.unlocked();
+        flash.set_parallelism(self.parallelism);
+        flash
+    }
+
+    /// Selects the newest valid page and erases all other pages
+    fn mount(&mut self, flash: &mut UnlockedFlash) -> Result<(), super::Error> {
+        // A second valid page is left when the old page was not erased after a transfer
+        let mut active: Option<(usize, u32)> = None;
+        for page in 0..N {
+            let (state, seq) = self.header(page);
+            if state != VALID {
+                continue;
+            }
+            match active {
+                Some((_, newest)) if newest > seq => self.erase(flash, page)?,
+                Some((older, _)) => {
+                    self.erase(flash, older)?;
+                    active = Some((page, seq));
+                }
+                None => active = Some((page, seq)),
+            }
+        }
+
+        // Receiving pages of interrupted transfers and partially erased pages
+        for page in 0..N {
+            if active.map(|(a, _)| a) != Some(page) && !self.is_erased(page) {
+                self.erase(flash, page)?;
+            }
+        }
+
+        match active {
+            Some((page, _)) => {
+                self.active = page;
+                let records = self.page(page)[HEADER_SIZE..].chunks_exact(RECORD_SIZE);
+                let used = records.take_while(|r| r.iter().any(|&b| b != 0xff)).count();
+                self.next = HEADER_SIZE + used * RECORD_SIZE;
+            }
+            None => {
+                // Format
+                self.program_header(flash, 0, 0, VALID)?;
+                self.active = 0;
+                self.next = HEADER_SIZE;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the newest value of a word, erased words read as `0xffff_ffff`
+    fn word(&mut self, index: usize) -> u32 {
+        self.find(self.active, self.next, index).unwrap_or(ERASED)
+    }
+
+    /// Finds the newest valid record of a word before `end`
+    fn find(&mut self, page: usize, end: usize, index: usize) -> Option<u32> {
+        let records = self.page(page)[HEADER_SIZE..end].chunks_exact(RECORD_SIZE);
+        for record in records.rev() {
+            let (i, data) = (le_u32(&record[0..4]), le_u32(&record[4..8]));
+            if i as usize == index && le_u32(&record[8..12]) == self.checksum(i, data) {
+                return Some(data);
+            }
+        }
+        None
+    }
+
+    fn append(
+        &mut self,
+        flash: &mut UnlockedFlash,
+        index: usize,
+        data: u32,
+    ) -> Result<(), super::Error> {
+        if self.next + RECORD_SIZE > self.sectors[self.active].size {
+            return self.transfer(flash, index, data);
+        }
+        // Slot is skipped even if programming fails
+        let offset = self.next;
+        self.next += RECORD_SIZE;
+        self.program_record(flash, self.active, offset, index, data)
+    }
+
+    /// Copies the newest value of every word to the next page, then erases the full page
+    fn transfer(
+        &mut self,
+        flash: &mut UnlockedFlash,
+        index: usize,
+        data: u32,
+    ) -> Result<(), super::Error> {
+        let old = self.active;
+        let new = (old + 1) % N;
+        if !self.is_erased(new) {
+            self.erase(flash, new)?;
+        }
+
+        let (_, seq) = self.header(old);
+        self.program_header(flash, new, seq + 1, RECEIVING)?;
+        let mut next = HEADER_SIZE;
+        for i in 0..self.words {
+            let value = if i == index {
+                data
+            } else {
+                self.find(old, self.next, i).unwrap_or(ERASED)
+            };
+            if value != ERASED {
+                self.program_record(flash, new, next, i, value)?;
+                next += RECORD_SIZE;
+            }
+        }
+        self.program_header(flash, new, seq + 1, VALID)?;
+
+        self.active = new;
+        self.next = next;
+        self.erase(flash, old)
+    }
+
+    fn erase(&self, flash: &mut UnlockedFlash, page: usize) -> Result<(), super::Error> {
+        flash.erase(self.sectors[page].number)
+    }
+
+    fn program_header(
+        &mut self,
+        flash: &mut UnlockedFlash,
+        page: usize,
+        seq: u32,
+        state: u32,
+    ) -> Result<(), super::Error> {
+        let offset = self.sectors[page].offset;
+        // Sequence number is valid before the state is
+        flash.program(offset + 4, seq.to_le_bytes().iter())?;
+        flash.program(offset, state.to_le_bytes().iter())
+    }
+
+    fn program_record(
+        &mut self,
+        flash: &mut UnlockedFlash,
+        page: usize,
+        offset: usize,
+        index: usize,
+        data: u32,
+    ) -> Result<(), super::Error> {
+        let index = index as u32;
+        let mut record = [0; RECORD_SIZE];
+        record[0..4].copy_from_slice(&index.to_le_bytes());
+        record[4..8].copy_from_slice(&data.to_le_bytes());
+        record[8..12].copy_from_slice(&self.checksum(index, data).to_le_bytes());
+        flash.program(self.sectors[page].offset + offset, record.iter())
+    }
+
+    fn checksum(&mut self, index: u32, data: u32) -> u32 {
+        self.crc.init();
+        self.crc.update(&[index, data])
+    }
+
+    /// Returns the state and sequence number of a page
+    fn header(&self, page: usize) -> (u32, u32) {
+        let page = self.page(page);
+        (le_u32(&page[0..4]), le_u32(&page[4..8]))
+    }
+
+    fn is_erased(&self, page: usize) -> bool {
+        self.page(page).iter().all(|&b| b == 0xff)
+    }
+
+    fn page(&self, page: usize) -> &'static [u8] {
+        let sector = &self.sectors[page];
+        unsafe { slice::from_raw_parts((self.base + sector.offset) as *const u8, sector.size) }
+    }
+}
+
+fn le_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
@@ -0,0 +1,338 @@
+//! Option bytes
+//!
+//! Option bytes are read from `OPTCR` and programmed in a session which unlocks `OPTKEYR`
+//! and locks it again when dropped:
+//!
+//! ```
+//! use stm32f4xx_hal::{
+//!     flash::{BorLevel, FlashExt, LockedFlash},
+//!     pac,
+//! };
+//!
+//! let dp = pac::Peripherals::take().unwrap();
+//! let mut flash = LockedFlash::new(dp.FLASH);
+//! let mut options = flash.unlocked_options();
+//! let mut ob = options.read();
+//! ob.bor_level = BorLevel::Level3;
+//! ob.write_protection |= 1 << 0;
+//! options.program(&ob).unwrap();
+//! // Read-out protection changes are loaded at reset
+//! options.reload();
+//! ```
+
+use super::{Error, FLASH};
+
+const OPT_KEY1: u32 = 0x0819_2A3B;
+const OPT_KEY2: u32 = 0x4C5D_6E7F;
+
+const OPTLOCK: u32 = 1 << 0;
+const OPTSTRT: u32 = 1 << 1;
+const BOR_LEV_SHIFT: u32 = 2;
+#[cfg(any(feature = "gpio-f427", feature = "gpio-f469"))]
+const BFB2: u32 = 1 << 4;
+const WDG_SW: u32 = 1 << 5;
+const NRST_STOP: u32 = 1 << 6;
+const NRST_STDBY: u32 = 1 << 7;
+const RDP_SHIFT: u32 = 8;
+const NWRP_SHIFT: u32 = 16;
+const NWRP_MASK: u32 = 0xfff;
+#[cfg(any(feature = "gpio-f427", feature = "gpio-f469"))]
+const DB1M: u32 = 1 << 30;
+#[cfg(any(feature = "gpio-f427", feature = "gpio-f446", feature = "gpio-f469"))]
+const SPRMOD: u32 = 1 << 31;
+
+/// Bits of `OPTCR` and `OPTCR1` holding option bytes
+#[cfg(any(feature = "gpio-f427", feature = "gpio-f469"))]
+const OPTCR_MASK: u32 = 0xcfff_fffc;
+#[cfg(feature = "gpio-f446")]
+const OPTCR_MASK: u32 = 0x8fff_ffec;
+#[cfg(not(any(feature = "gpio-f427", feature = "gpio-f446", feature = "gpio-f469")))]
+const OPTCR_MASK: u32 = 0x0fff_ffec;
+
+/// Read-out protection level
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadProtection {
+    /// No protection
+    Level0,
+    /// Flash can not be read by debugger or from RAM, regression to level 0 erases the flash
+    Level1,
+    /// Debug is disabled and option bytes can not be changed anymore. **Irreversible**
+    Level2,
+}
+
+impl ReadProtection {
+    const fn from_bits(rdp: u8) -> Self {
+        match rdp {
+            0xaa => Self::Level0,
+            0xcc => Self::Level2,
+            _ => Self::Level1,
+        }
+    }
+
+    const fn bits(self) -> u8 {
+        match self {
+            Self::Level0 => 0xaa,
+            Self::Level1 => 0x55,
+            Self::Level2 => 0xcc,
+        }
+    }
+}
+
+/// Brownout reset threshold
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BorLevel {
+    /// About 2.7 V
+    Level3 = 0b00,
+    /// About 2.4 V
+    Level2 = 0b01,
+    /// About 2.1 V
+    Level1 = 0b10,
+    /// Reset at power down threshold only, about 1.8 V
+    Off = 0b11,
+}
+
+impl BorLevel {
+    const fn from_bits(bits: u32) -> Self {
+        match bits & 0b11 {
+            0b00 => Self::Level3,
+            0b01 => Self::Level2,
+            0b10 => Self::Level1,
+            _ => Self::Off,
+        }
+    }
+}
+
+/// Sector protection mode
+#[cfg(any(feature = "gpio-f427", feature = "gpio-f446", feature = "gpio-f469"))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProtectionMode {
+    /// Protected sectors can not be erased or programmed
+    Write,
+    /// Protected sectors can only be fetched as code (PCROP).
+    ///
+    /// Returning to write protection requires a read-out protection regression from level 1.
+    Pcrop,
+}
+
+/// Option bytes
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OptionBytes {
+    pub read_protection: ReadProtection,
+    pub bor_level: BorLevel,
+    /// Independent watchdog is started by software, otherwise by hardware at reset
+    pub watchdog_sw: bool,
+    /// Reset is generated when entering Stop mode
+    pub reset_on_stop: bool,
+    /// Reset is generated when entering Standby mode
+    pub reset_on_standby: bool,
+    /// Protected sectors, bit `n` is sector `n`
+    pub write_protection: u32,
+    /// Mode of `write_protection`
+    #[cfg(any(feature = "gpio-f427", feature = "gpio-f446", feature = "gpio-f469"))]
+    pub protection_mode: ProtectionMode,
+    /// 1 MB flash is organized in two banks
+    #[cfg(any(feature = "gpio-f427", feature = "gpio-f469"))]
+    pub dual_bank_1m: bool,
+    /// Boot from the second bank if it contains a valid stack pointer
+    #[cfg(any(feature = "gpio-f427", feature = "gpio-f469"))]
+    pub boot_bank2: bool,
+}
+
+impl OptionBytes {
+    #[cfg_attr(
+        not(any(feature = "gpio-f427", feature = "gpio-f469")),
+        allow(unused_variables)
+    )]
+    fn from_bits(optcr: u32, optcr1: u32) -> Self {
+        #[cfg(any(feature = "gpio-f427", feature = "gpio-f469"))]
+        let nwrp =
+            ((optcr >> NWRP_SHIFT) & NWRP_MASK) | (((optcr1 >> NWRP_SHIFT) & NWRP_MASK) << 12);
+        #[cfg(not(any(feature = "gpio-f427", feature = "gpio-f469")))]
+        let nwrp = (optcr >> NWRP_SHIFT) & NWRP_MASK;
+
+        #[cfg(any(feature = "gpio-f427", feature = "gpio-f446", feature = "gpio-f469"))]
+        let protection_mode = if optcr & SPRMOD != 0 {
+            ProtectionMode::Pcrop
+        } else {
+            ProtectionMode::Write
+        };
+
+        // Write protection is active low, PCROP is active high
+        #[cfg(any(feature = "gpio-f427", feature = "gpio-f446", feature = "gpio-f469"))]
+        let write_protection = match protection_mode {
+            ProtectionMode::Write => !nwrp & Self::sectors_mask(),
+            ProtectionMode::Pcrop => nwrp,
+        };
+        #[cfg(not(any(feature = "gpio-f427", feature = "gpio-f446", feature = "gpio-f469")))]
+        let write_protection = !nwrp & Self::sectors_mask();
+
+        Self {
+            read_protection: ReadProtection::from_bits((optcr >> RDP_SHIFT) as u8),
+            bor_level: BorLevel::from_bits(optcr >> BOR_LEV_SHIFT),
+            watchdog_sw: optcr & WDG_SW != 0,
+            reset_on_stop: optcr & NRST_STOP == 0,
+            reset_on_standby: optcr & NRST_STDBY == 0,
+            write_protection,
+            #[cfg(any(feature = "gpio-f427", feature = "gpio-f446", feature = "gpio-f469"))]
+            protection_mode,
+            #[cfg(any(feature = "gpio-f427", feature = "gpio-f469"))]
+            dual_bank_1m: optcr & DB1M != 0,
+            #[cfg(any(feature = "gpio-f427", feature = "gpio-f469"))]
+            boot_bank2: optcr & BFB2 != 0,
+        }
+    }
+
+    /// Returns `OPTCR` and `OPTCR1` option bits
+    fn bits(&self) -> (u32, u32) {
+        #[cfg(any(feature = "gpio-f427", feature = "gpio-f446", feature = "gpio-f469"))]
+        let nwrp = match self.protection_mode {
+            ProtectionMode::Write => !self.write_protection & Self::sectors_mask(),
+            ProtectionMode::Pcrop => self.write_protection & Self::sectors_mask(),
+        };
+        #[cfg(not(any(feature = "gpio-f427", feature = "gpio-f446", feature = "gpio-f469")))]
+        let nwrp = !self.write_protection & Self::sectors_mask();
+
+        let mut optcr = ((self.bor_level as u32) << BOR_LEV_SHIFT)
+            | (u32::from(self.read_protection.bits()) << RDP_SHIFT)
+            | ((nwrp & NWRP_MASK) << NWRP_SHIFT);
+        if self.watchdog_sw {
+            optcr |= WDG_SW;
+        }
+        if !self.reset_on_stop {
+            optcr |= NRST_STOP;
+        }
+        if !self.reset_on_standby {
+            optcr |= NRST_STDBY;
+        }
+        #[cfg(any(feature = "gpio-f427", feature = "gpio-f446", feature = "gpio-f469"))]
+        if self.protection_mode == ProtectionMode::Pcrop {
+            optcr |= SPRMOD;
+        }
+        #[cfg(any(feature = "gpio-f427", feature = "gpio-f469"))]
+        {
+            if self.dual_bank_1m {
+                optcr |= DB1M;
+            }
+            if self.boot_bank2 {
+                optcr |= BFB2;
+            }
+        }
+
+        #[cfg(any(feature = "gpio-f427", feature = "gpio-f469"))]
+        let optcr1 = ((nwrp >> 12) & NWRP_MASK) << NWRP_SHIFT;
+        #[cfg(not(any(feature = "gpio-f427", feature = "gpio-f469")))]
+        let optcr1 = 0;
+
+        (optcr, optcr1)
+    }
+
+    /// Sectors with protection bits
+    const fn sectors_mask() -> u32 {
+        if cfg!(any(feature = "gpio-f427", feature = "gpio-f469")) {
+            0xff_ffff
+        } else {
+            NWRP_MASK
+        }
+    }
+}
+
+/// Reads the option bytes
+pub(super) fn read(flash: &FLASH) -> OptionBytes {
+    #[cfg(any(feature = "gpio-f427", feature = "gpio-f469"))]
+    let optcr1 = flash.optcr1().read().bits();
+    #[cfg(not(any(feature = "gpio-f427", feature = "gpio-f469")))]
+    let optcr1 = 0;
+
+    OptionBytes::from_bits(flash.optcr().read().bits(), optcr1)
+}
+
+/// Result of [`FlashExt::unlocked_options`](super::FlashExt::unlocked_options)
+pub struct UnlockedOptionBytes<'a> {
+    flash: &'a mut FLASH,
+}
+
+impl<'a> UnlockedOptionBytes<'a> {
+    pub(super) fn new(flash: &'a mut FLASH) -> Self {
+        flash.optkeyr().write(|w| unsafe { w.bits(OPT_KEY1) });
+        flash.optkeyr().write(|w| unsafe { w.bits(OPT_KEY2) });
+        assert!(flash.optcr().read().bits() & OPTLOCK == 0);
+        Self { flash }
+    }
+
+    /// Reads the option bytes
+    pub fn read(&self) -> OptionBytes {
+        read(self.flash)
+    }
+
+    /// Programs the option bytes
+    ///
+    /// Most options take effect immediately, read-out protection changes are loaded at reset,
+    /// see [`UnlockedOptionBytes::reload`]. Regression from read-out protection level 1 to 0
+    /// mass erases the flash.
+    ///
+    /// Returns [`Error::ReadProtectionLevel2`] if `ob` selects level 2, which can only be set
+    /// with [`UnlockedOptionBytes::set_read_protection_level2`].
+    pub fn program(&mut self, ob: &OptionBytes) -> Result<(), Error> {
+        if ob.read_protection == ReadProtection::Level2 {
+            return Err(Error::ReadProtectionLevel2);
+        }
+        self.write(ob)
+    }
+
+    /// Sets read-out protection level 2, keeping the other option bytes
+    ///
+    /// The level is loaded at reset, see [`UnlockedOptionBytes::reload`].
+    ///
+    /// # Safety
+    ///
+    /// Level 2 is irreversible: debug is disabled and the option bytes, including this level,
+    /// can never be changed again.
+    pub unsafe fn set_read_protection_level2(&mut self) -> Result<(), Error> {
+        let mut ob = self.read();
+        ob.read_protection = ReadProtection::Level2;
+        self.write(&ob)
+    }
+
+    #[cfg_attr(
+        not(any(feature = "gpio-f427", feature = "gpio-f469")),
+        allow(unused_variables)
+    )]
+    fn write(&mut self, ob: &OptionBytes) -> Result<(), Error> {
+        let (optcr, optcr1) = ob.bits();
+
+        while self.flash.sr().read().bsy().bit() {}
+
+        #[cfg(any(feature = "gpio-f427", feature = "gpio-f469"))]
+        self.flash
+            .optcr1()
+            .modify(|r, w| unsafe { w.bits((r.bits() & !(NWRP_MASK << NWRP_SHIFT)) | optcr1) });
+
+        self.flash
+            .optcr()
+            .modify(|r, w| unsafe { w.bits((r.bits() & !OPTCR_MASK) | optcr) });
+        self.flash
+            .optcr()
+            .modify(|r, w| unsafe { w.bits(r.bits() | OPTSTRT) });
+
+        while self.flash.sr().read().bsy().bit() {}
+        Error::read(self.flash).map(Err).unwrap_or(Ok(()))
+    }
+
+    /// Resets the system to load the option bytes
+    pub fn reload(self) -> ! {
+        cortex_m::peripheral::SCB::sys_reset()
+    }
+}
+
+/// Locks option bytes when leaving scope
+impl Drop for UnlockedOptionBytes<'_> {
+    fn drop(&mut self) {
+        self.flash
+            .optcr()
+            .modify(|r, w| unsafe { w.bits(r.bits() | OPTLOCK) });
+    }
+}
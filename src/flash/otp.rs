@@ -0,0 +1,82 @@
+//! One-time-programmable area
+//!
+//! 512 bytes of OTP are organized in 16 blocks of 32 bytes. Each block can be locked against
+//! further programming by its lock byte:
+//!
+//! ```
+//! use stm32f4xx_hal::{
+//!     flash::{FlashExt, LockedFlash},
+//!     pac,
+//! };
+//!
+//! let dp = pac::Peripherals::take().unwrap();
+//! let serial_number = [0x42; 32];
+//! let mut flash = LockedFlash::new(dp.FLASH);
+//! if flash.read_otp(0).iter().all(|&b| b == 0xff) {
+//!     let mut unlocked = flash.unlocked();
+//!     unlocked.program_otp(0, &serial_number).unwrap();
+//!     unlocked.lock_otp(0).unwrap();
+//! }
+//! ```
+
+use super::{Error, UnlockedFlash};
+
+/// Number of OTP blocks
+pub const OTP_BLOCKS: u8 = 16;
+/// Size of an OTP block in bytes
+pub const OTP_BLOCK_SIZE: usize = 32;
+
+const OTP_ADDRESS: usize = 0x1fff_7800;
+const OTP_LOCK_ADDRESS: usize = 0x1fff_7a00;
+
+fn block_address(block: u8) -> usize {
+    assert!(block < OTP_BLOCKS);
+    OTP_ADDRESS + block as usize * OTP_BLOCK_SIZE
+}
+
+fn lock_address(block: u8) -> usize {
+    assert!(block < OTP_BLOCKS);
+    OTP_LOCK_ADDRESS + block as usize
+}
+
+/// Returns a copy of an OTP block
+pub(super) fn read(block: u8) -> [u8; OTP_BLOCK_SIZE] {
+    // Volatile, the block changes when it is programmed
+    unsafe { core::ptr::read_volatile(block_address(block) as *const _) }
+}
+
+/// Returns true if the lock byte of a block is programmed
+pub(super) fn is_locked(block: u8) -> bool {
+    unsafe { *(lock_address(block) as *const u8) != 0xff }
+}
+
+impl UnlockedFlash<'_> {
+    /// Programs an OTP block with the selected parallelism
+    ///
+    /// Bits can only be cleared, an OTP block can not be erased. Returns
+    /// [`Error::OtpLocked`] if the block is locked.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `block` is not less than [`OTP_BLOCKS`].
+    pub fn program_otp(&mut self, block: u8, data: &[u8; OTP_BLOCK_SIZE]) -> Result<(), Error> {
+        if is_locked(block) {
+            return Err(Error::OtpLocked);
+        }
+        self.program_at(block_address(block) as *mut u8, 0, data.iter())
+    }
+
+    /// Permanently locks an OTP block against programming
+    ///
+    /// # Panics
+    ///
+    /// Panics if `block` is not less than [`OTP_BLOCKS`].
+    pub fn lock_otp(&mut self, block: u8) -> Result<(), Error> {
+        if is_locked(block) {
+            return Ok(());
+        }
+        let result = self.program_word(lock_address(block) as *mut u8, &[0]);
+        self.flash.cr().modify(|_, w| w.pg().clear_bit());
+        result
+    }
+}
@@ -2,9 +2,9 @@ use core::ops::Deref;
 
 use crate::gpio;
 use crate::i2c::{Error, NoAcknowledgeSource};
+use crate::pac;
 use crate::pac::fmpi2c1 as i2c1;
-use crate::pac::{self, RCC};
-use crate::rcc::{BusClock, Enable, Reset};
+use crate::rcc::{BusClock, Clocks, Enable, Reset};
 use fugit::{HertzU32 as Hertz, RateExtU32};
 
 // Old names
@@ -23,28 +23,30 @@ pub trait Instance:
     + BusClock
     + gpio::alt::I2cCommon
 {
-    fn clock_hsi(rcc: &crate::pac::rcc::RegisterBlock);
+    /// Returns the kernel clock selected with `CFGR`
+    fn kernel_clock(clocks: &Clocks) -> Hertz;
 }
 
 macro_rules! i2c {
-    ($I2C:ty, $i2csel:ident, $I2Calias:ident) => {
+    ($I2C:ty, $clk:ident, $I2Calias:ident) => {
         pub type $I2Calias = I2c<$I2C>;
 
         impl Instance for $I2C {
-            fn clock_hsi(rcc: &crate::pac::rcc::RegisterBlock) {
-                rcc.dckcfgr2().modify(|_, w| w.$i2csel().hsi());
+            fn kernel_clock(clocks: &Clocks) -> Hertz {
+                clocks.$clk()
             }
         }
     };
 }
 
 #[cfg(feature = "fmpi2c1")]
-i2c!(pac::FMPI2C1, fmpi2c1sel, FMPI2c1);
+i2c!(pac::FMPI2C1, fmpi2c1_clk, FMPI2c1);
 
 /// I2C FastMode+ abstraction
 pub struct I2c<I2C: Instance> {
     i2c: I2C,
     pins: (I2C::Scl, I2C::Sda),
+    mode: Mode,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -95,6 +97,7 @@ pub trait I2cExt: Sized + Instance {
         self,
         pins: (impl Into<Self::Scl>, impl Into<Self::Sda>),
         mode: impl Into<Mode>,
+        clocks: &Clocks,
     ) -> I2c<Self>;
 }
 
@@ -103,8 +106,9 @@ impl<I2C: Instance> I2cExt for I2C {
         self,
         pins: (impl Into<Self::Scl>, impl Into<Self::Sda>),
         mode: impl Into<Mode>,
+        clocks: &Clocks,
     ) -> I2c<Self> {
-        I2c::new(self, pins, mode)
+        I2c::new(self, pins, mode, clocks)
     }
 }
 
@@ -113,6 +117,7 @@ impl<I2C: Instance> I2c<I2C> {
         i2c: I2C,
         pins: (impl Into<I2C::Scl>, impl Into<I2C::Sda>),
         mode: impl Into<Mode>,
+        clocks: &Clocks,
     ) -> Self {
         unsafe {
             // Enable and reset clock.
@@ -121,59 +126,68 @@ impl<I2C: Instance> I2c<I2C> {
         }
 
         let pins = (pins.0.into(), pins.1.into());
+        let mode = mode.into();
 
-        let i2c = I2c { i2c, pins };
-        i2c.i2c_init(mode);
+        let i2c = I2c { i2c, pins, mode };
+        i2c.i2c_init(mode, I2C::kernel_clock(clocks));
         i2c
     }
 
+    /// Recomputes bus timings from `clocks` returned by
+    /// [`CFGR::refreeze`](crate::rcc::CFGR::refreeze).
+    ///
+    /// Must not be called during a transfer.
+    pub fn configure(&mut self, clocks: &Clocks) {
+        self.i2c_init(self.mode, I2C::kernel_clock(clocks));
+    }
+
     pub fn release(self) -> (I2C, (I2C::Scl, I2C::Sda)) {
         (self.i2c, self.pins)
     }
 }
 
 impl<I2C: Instance> I2c<I2C> {
-    fn i2c_init(&self, mode: impl Into<Mode>) {
+    fn i2c_init(&self, mode: impl Into<Mode>, clock: Hertz) {
         let mode = mode.into();
 
         // Make sure the I2C unit is disabled so we can configure it
         self.i2c.cr1().modify(|_, w| w.pe().clear_bit());
 
-        // NOTE(unsafe) this reference will only be used for atomic writes with no side effects.
-        let rcc = unsafe { &(*RCC::ptr()) };
-        I2C::clock_hsi(rcc);
-
         // Calculate settings for I2C speed modes
-        let presc;
         let scldel;
         let sdadel;
         let sclh;
         let scll;
 
-        // We're using the HSI clock to keep things simple so this is going to be always 16 MHz
-        const FREQ: u32 = 16_000_000;
+        // Timings are given for a prescaled clock of 4 MHz in standard mode, 8 MHz in fast mode
+        // and 16 MHz in fast mode+
+        let (tick, frequency) = match mode {
+            Mode::Standard { frequency } => (4_000_000, frequency),
+            Mode::Fast { frequency } => (8_000_000, frequency),
+            Mode::FastPlus { frequency } => (16_000_000, frequency),
+        };
+        let presc = crate::min_u32(((clock.raw() + tick - 1) / tick).saturating_sub(1), 15);
+        let half_period = (clock.raw() / (presc + 1) / 2) / frequency.raw();
+        let presc = presc as u8;
 
         // Normal I2C speeds use a different scaling than fast mode below and fast mode+ even more
         // below
         match mode {
-            Mode::Standard { frequency } => {
-                presc = 3;
-                scll = crate::max_u32((((FREQ >> presc) >> 1) / frequency.raw()) - 1, 255) as u8;
-                sclh = scll - 4;
+            Mode::Standard { .. } => {
+                scll = crate::min_u32(half_period.saturating_sub(1), 255) as u8;
+                sclh = scll.saturating_sub(4);
                 sdadel = 2;
                 scldel = 4;
             }
-            Mode::Fast { frequency } => {
-                presc = 1;
-                scll = crate::max_u32((((FREQ >> presc) >> 1) / frequency.raw()) - 1, 255) as u8;
-                sclh = scll - 6;
+            Mode::Fast { .. } => {
+                scll = crate::min_u32(half_period.saturating_sub(1), 255) as u8;
+                sclh = scll.saturating_sub(6);
                 sdadel = 2;
                 scldel = 3;
             }
-            Mode::FastPlus { frequency } => {
-                presc = 0;
-                scll = crate::max_u32((((FREQ >> presc) >> 1) / frequency.raw()) - 4, 255) as u8;
-                sclh = scll - 2;
+            Mode::FastPlus { .. } => {
+                scll = crate::min_u32(half_period.saturating_sub(4), 255) as u8;
+                sclh = scll.saturating_sub(2);
                 sdadel = 0;
                 scldel = 2;
             }
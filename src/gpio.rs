@@ -66,6 +66,8 @@ mod erased;
 pub use erased::{AnyPin, ErasedPin};
 mod exti;
 pub use exti::ExtiPin;
+#[cfg(feature = "async")]
+pub use exti::{on_exti_interrupt, ExtiInput};
 mod dynamic;
 pub use dynamic::{Dynamic, DynamicPin};
 mod hal_02;
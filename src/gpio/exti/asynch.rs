@@ -0,0 +1,213 @@
+//! Waiting for pin level or edge with [`embedded_hal_async::digital::Wait`]
+//!
+//! EXTI interrupts of used lines must be unmasked in the NVIC and
+//! [`on_exti_interrupt`] called from their handlers.
+//! One handler serves all lines of the grouped `EXTI9_5` and `EXTI15_10` vectors:
+//!
+//! ```ignore
+//! #[interrupt]
+//! fn EXTI15_10() {
+//!     gpio::on_exti_interrupt();
+//! }
+//! ```
+
+use core::convert::Infallible;
+use core::future::poll_fn;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::task::Poll;
+
+use atomic_waker::AtomicWaker;
+use embedded_hal::digital::InputPin;
+
+use super::ExtiPin;
+use crate::gpio::{Edge, PinExt};
+use crate::pac::EXTI;
+use crate::syscfg::SysCfg;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NEW: AtomicWaker = AtomicWaker::new();
+static WAKERS: [AtomicWaker; 16] = [NEW; 16];
+/// Lines with a task waiting in [`ExtiInput`], other lines are left to their own handlers
+static WAITING: AtomicU32 = AtomicU32::new(0);
+
+/// Wakes the tasks waiting for pending EXTI lines 0-15.
+///
+/// Must be called from `EXTIx` interrupt handlers of lines used by [`ExtiInput`].
+/// Lines without a waiting [`ExtiInput`] are neither masked nor cleared.
+pub fn on_exti_interrupt() {
+    let exti = unsafe { &*EXTI::ptr() };
+    let pending =
+        exti.pr().read().bits() & exti.imr().read().bits() & WAITING.load(Ordering::Relaxed);
+    // Mask lines until the woken tasks wait again, masked line means event occurred
+    cortex_m::interrupt::free(|_| {
+        exti.imr()
+            .modify(|r, w| unsafe { w.bits(r.bits() & !pending) });
+    });
+    exti.pr().write(|w| unsafe { w.bits(pending) });
+
+    for (line, waker) in WAKERS.iter().enumerate() {
+        if pending & (1 << line) != 0 {
+            waker.wake();
+        }
+    }
+}
+
+/// Input pin which can asynchronously wait for a level or an edge
+pub struct ExtiInput<PIN> {
+    pin: PIN,
+}
+
+impl<PIN> ExtiInput<PIN>
+where
+    PIN: PinExt + ExtiPin + InputPin<Error = Infallible>,
+{
+    /// Makes EXTI line sensitive to `pin`.
+    ///
+    /// Only one pin with the same number can be used at a time.
+    pub fn new(mut pin: PIN, syscfg: &mut SysCfg) -> Self {
+        pin.make_interrupt_source(syscfg);
+        Self { pin }
+    }
+
+    /// Releases the pin
+    pub fn release(self) -> PIN {
+        self.pin
+    }
+
+    /// Returns `true` if the pin is high
+    pub fn is_high(&mut self) -> bool {
+        self.pin.is_high().unwrap_or_else(|e| match e {})
+    }
+
+    /// Returns `true` if the pin is low
+    pub fn is_low(&mut self) -> bool {
+        !self.is_high()
+    }
+
+    /// Waits for `edge` on the pin or for `done` to return `true`
+    async fn wait_for(&mut self, edge: Edge, done: impl Fn(&mut Self) -> bool) {
+        let line = self.pin.pin_id();
+        let waiting = Waiting::new(line);
+        // NOTE(unsafe) only bits of this line are modified
+        let exti = unsafe { &*EXTI::ptr() };
+
+        cortex_m::interrupt::free(|_| {
+            self.pin
+                .trigger_on_edge(unsafe { &mut EXTI::steal() }, edge);
+            self.pin.clear_interrupt_pending_bit();
+            WAITING.fetch_or(waiting.mask, Ordering::Relaxed);
+            exti.imr()
+                .modify(|r, w| unsafe { w.bits(r.bits() | waiting.mask) });
+        });
+
+        // Level could change before interrupt was enabled
+        if done(self) {
+            return;
+        }
+
+        poll_fn(|cx| {
+            WAKERS[line as usize].register(cx.waker());
+            if exti.imr().read().bits() & waiting.mask == 0 {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+    }
+
+    /// Waits until the pin is high
+    pub async fn wait_for_high(&mut self) {
+        self.wait_for(Edge::Rising, Self::is_high).await
+    }
+
+    /// Waits until the pin is low
+    pub async fn wait_for_low(&mut self) {
+        self.wait_for(Edge::Falling, Self::is_low).await
+    }
+
+    /// Waits for a rising edge
+    pub async fn wait_for_rising_edge(&mut self) {
+        self.wait_for(Edge::Rising, |_| false).await
+    }
+
+    /// Waits for a falling edge
+    pub async fn wait_for_falling_edge(&mut self) {
+        self.wait_for(Edge::Falling, |_| false).await
+    }
+
+    /// Waits for any edge
+    pub async fn wait_for_any_edge(&mut self) {
+        self.wait_for(Edge::RisingFalling, |_| false).await
+    }
+}
+
+/// Masks the line and hands it back to other users when the wait finishes or is cancelled
+struct Waiting {
+    mask: u32,
+}
+
+impl Waiting {
+    fn new(line: u8) -> Self {
+        Self { mask: 1 << line }
+    }
+}
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        // NOTE(unsafe) only bits of this line are modified
+        let exti = unsafe { &*EXTI::ptr() };
+        cortex_m::interrupt::free(|_| {
+            exti.imr()
+                .modify(|r, w| unsafe { w.bits(r.bits() & !self.mask) });
+            WAITING.fetch_and(!self.mask, Ordering::Relaxed);
+        });
+    }
+}
+
+impl<PIN> embedded_hal::digital::ErrorType for ExtiInput<PIN> {
+    type Error = Infallible;
+}
+
+impl<PIN> InputPin for ExtiInput<PIN>
+where
+    PIN: PinExt + ExtiPin + InputPin<Error = Infallible>,
+{
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(Self::is_high(self))
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(Self::is_low(self))
+    }
+}
+
+impl<PIN> embedded_hal_async::digital::Wait for ExtiInput<PIN>
+where
+    PIN: PinExt + ExtiPin + InputPin<Error = Infallible>,
+{
+    async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+        Self::wait_for_high(self).await;
+        Ok(())
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+        Self::wait_for_low(self).await;
+        Ok(())
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+        Self::wait_for_rising_edge(self).await;
+        Ok(())
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+        Self::wait_for_falling_edge(self).await;
+        Ok(())
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+        Self::wait_for_any_edge(self).await;
+        Ok(())
+    }
+}
@@ -17,13 +17,16 @@ use common::{Hal02Operation, Hal1Operation};
 
 pub mod dma;
 
-#[derive(Debug, Eq, PartialEq)]
+#[cfg(feature = "async")]
+mod asynch;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum DutyCycle {
     Ratio2to1,
     Ratio16to9,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Mode {
     Standard {
         frequency: Hertz,
@@ -72,6 +75,7 @@ impl From<Hertz> for Mode {
 pub struct I2c<I2C: Instance> {
     i2c: I2C,
     pins: (I2C::Scl, I2C::Sda),
+    mode: Mode,
 }
 
 pub trait Instance:
@@ -82,6 +86,9 @@ pub trait Instance:
     + Reset
     + gpio::alt::I2cCommon
 {
+    #[cfg(feature = "async")]
+    #[doc(hidden)]
+    fn waker() -> &'static atomic_waker::AtomicWaker;
 }
 
 // Implemented by all I2C instances
@@ -89,7 +96,13 @@ macro_rules! i2c {
     ($I2C:ty: $I2c:ident) => {
         pub type $I2c = I2c<$I2C>;
 
-        impl Instance for $I2C {}
+        impl Instance for $I2C {
+            #[cfg(feature = "async")]
+            fn waker() -> &'static atomic_waker::AtomicWaker {
+                static WAKER: atomic_waker::AtomicWaker = atomic_waker::AtomicWaker::new();
+                &WAKER
+            }
+        }
     };
 }
 
@@ -133,12 +146,21 @@ impl<I2C: Instance> I2c<I2C> {
         }
 
         let pins = (pins.0.into(), pins.1.into());
+        let mode = mode.into();
 
-        let i2c = I2c { i2c, pins };
+        let i2c = I2c { i2c, pins, mode };
         i2c.i2c_init(mode, clocks.pclk1());
         i2c
     }
 
+    /// Recomputes bus timings from `clocks` returned by
+    /// [`CFGR::refreeze`](crate::rcc::CFGR::refreeze).
+    ///
+    /// Must not be called during a transfer.
+    pub fn configure(&mut self, clocks: &Clocks) {
+        self.i2c_init(self.mode, clocks.pclk1());
+    }
+
     pub fn release(self) -> (I2C, (I2C::Scl, I2C::Sda)) {
         (self.i2c, self.pins)
     }
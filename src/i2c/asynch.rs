@@ -0,0 +1,337 @@
+//! Interrupt driven [`embedded_hal_async::i2c::I2c`] implementation
+//!
+//! To use it, unmask both `I2Cx_EV` and `I2Cx_ER` interrupts in the NVIC
+//! and call [`I2c::on_interrupt`] from their handlers:
+//!
+//! ```ignore
+//! #[interrupt]
+//! fn I2C1_EV() {
+//!     I2c1::on_interrupt();
+//! }
+//!
+//! #[interrupt]
+//! fn I2C1_ER() {
+//!     I2c1::on_interrupt();
+//! }
+//! ```
+//!
+//! Dropping a future before it completes leaves the bus in the middle of a transfer.
+
+use core::future::poll_fn;
+use core::task::Poll;
+
+use super::{Address, Error, I2c, Instance};
+use crate::pac::i2c1;
+use embedded_hal::i2c::{Operation, SevenBitAddress, TenBitAddress};
+
+impl<I2C: Instance> I2c<I2C> {
+    /// Wakes the task waiting for the I2C event.
+    ///
+    /// Must be called from both `I2Cx_EV` and `I2Cx_ER` interrupt handlers.
+    pub fn on_interrupt() {
+        let i2c = unsafe { &*I2C::ptr() };
+        // Mask interrupts until the woken task waits for the next event
+        i2c.cr2().modify(|_, w| {
+            w.itevten().clear_bit();
+            w.itbufen().clear_bit();
+            w.iterren().clear_bit()
+        });
+        I2C::waker().wake();
+    }
+
+    /// Waits until `f` returns `true` for the status register or an error flag is set.
+    ///
+    /// Buffer interrupts (TxE, RxNE) are enabled only if `buf` is `true`.
+    async fn wait_sr1(&self, buf: bool, f: impl Fn(&i2c1::sr1::R) -> bool) -> Result<(), Error> {
+        poll_fn(|cx| {
+            I2C::waker().register(cx.waker());
+            match self.check_and_clear_error_flags() {
+                Err(e) => Poll::Ready(Err(e)),
+                Ok(sr1) if f(&sr1) => Poll::Ready(Ok(())),
+                Ok(_) => {
+                    self.i2c.cr2().modify(|_, w| {
+                        w.itevten().set_bit();
+                        w.iterren().set_bit();
+                        w.itbufen().bit(buf)
+                    });
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+
+    /// Sends (RE)START and Address, returns when address is acknowledged.
+    ///
+    /// ADDR flag is not cleared as reads need to configure ACK before.
+    async fn start_async(&mut self, addr: Address, read: bool) -> Result<(), Error> {
+        // Wait until a previous STOP condition finishes
+        while self.i2c.cr1().read().stop().bit_is_set() {}
+
+        // Clear all pending error bits
+        self.i2c.sr1().write(|w| unsafe { w.bits(0) });
+        // Send a START condition, set ACK bit for reading
+        self.i2c.cr1().modify(|_, w| {
+            if read {
+                w.ack().set_bit();
+            }
+            w.start().set_bit()
+        });
+
+        // Wait until START condition was generated
+        self.wait_sr1(false, |sr1| sr1.sb().bit_is_set()).await?;
+
+        // Set up current address, we're trying to talk to
+        match addr {
+            Address::Seven(addr) => {
+                self.i2c
+                    .dr()
+                    .write(|w| unsafe { w.bits((u16::from(addr) << 1) | u16::from(read)) });
+            }
+            Address::Ten(addr) => {
+                let [msbs, lsbs] = addr.to_be_bytes();
+                let header = ((msbs & 0b11) << 1) | 0b11110000;
+                self.i2c
+                    .dr()
+                    .write(|w| unsafe { w.bits(u16::from(header)) });
+                self.wait_sr1(false, |sr1| sr1.add10().bit_is_set())
+                    .await
+                    .map_err(Error::nack_addr)?;
+                self.i2c.dr().write(|w| unsafe { w.bits(u16::from(lsbs)) });
+                if read {
+                    // Address is sent in write direction first, switch to reading with a repeated START
+                    self.wait_sr1(false, |sr1| sr1.addr().bit_is_set())
+                        .await
+                        .map_err(Error::nack_addr)?;
+                    self.i2c.sr2().read();
+                    self.i2c.cr1().modify(|_, w| w.start().set_bit());
+                    self.wait_sr1(false, |sr1| sr1.sb().bit_is_set()).await?;
+                    self.i2c
+                        .dr()
+                        .write(|w| unsafe { w.bits(u16::from(header | 1)) });
+                }
+            }
+        }
+
+        // Wait until address was sent. If a NACK occurs, the ADDR bit will never be set.
+        self.wait_sr1(false, |sr1| sr1.addr().bit_is_set())
+            .await
+            .map_err(Error::nack_addr)
+    }
+
+    /// Generates STOP or repeated START after the current byte
+    fn end_transfer(&self, stop: bool) {
+        self.i2c.cr1().modify(|_, w| {
+            if stop {
+                w.stop().set_bit()
+            } else {
+                w.start().set_bit()
+            }
+        });
+    }
+
+    async fn write_bytes_async(&mut self, bytes: impl Iterator<Item = u8>) -> Result<(), Error> {
+        // Clear condition by reading SR2
+        self.i2c.sr2().read();
+
+        let mut sent = false;
+        for byte in bytes {
+            // Wait until we're ready for sending
+            self.wait_sr1(true, |sr1| sr1.tx_e().bit_is_set())
+                .await
+                .map_err(Error::nack_data)?;
+
+            // Push out a byte of data
+            self.i2c.dr().write(|w| unsafe { w.bits(u16::from(byte)) });
+            sent = true;
+        }
+
+        if sent {
+            // Wait until the last byte is transferred
+            self.wait_sr1(false, |sr1| sr1.btf().bit_is_set())
+                .await
+                .map_err(Error::nack_data)?;
+        }
+
+        Ok(())
+    }
+
+    /// Receives `len` bytes and finishes with STOP or repeated START.
+    ///
+    /// Follows the reference manual sequence for 1, 2 and more bytes
+    /// so the NACK is correctly placed independently of interrupt latency.
+    async fn read_bytes_async<'b>(
+        &mut self,
+        buffer: impl Iterator<Item = &'b mut u8>,
+        len: usize,
+        stop: bool,
+    ) -> Result<(), Error> {
+        match len {
+            0 => return Err(Error::Overrun),
+            1 => {
+                // NACK must be programmed before ADDR is cleared
+                self.i2c.cr1().modify(|_, w| w.ack().clear_bit());
+                self.i2c.sr2().read();
+                self.end_transfer(stop);
+            }
+            2 => {
+                // NACK the second byte
+                self.i2c.cr1().modify(|_, w| w.pos().set_bit());
+                self.i2c.sr2().read();
+                self.i2c.cr1().modify(|_, w| w.ack().clear_bit());
+            }
+            _ => {
+                self.i2c.sr2().read();
+            }
+        }
+
+        for (i, byte) in buffer.enumerate() {
+            match (len, len - i) {
+                (2, 2) => {
+                    // Both bytes received, SCL is stretched
+                    self.wait_sr1(false, |sr1| sr1.btf().bit_is_set()).await?;
+                    self.end_transfer(stop);
+                }
+                (2, 1) => {}
+                (_, 3) => {
+                    // N-2 in DR and N-1 in shift register, SCL is stretched
+                    self.wait_sr1(false, |sr1| sr1.btf().bit_is_set()).await?;
+                    self.i2c.cr1().modify(|_, w| w.ack().clear_bit());
+                }
+                (_, 2) => self.end_transfer(stop),
+                _ => self.wait_sr1(true, |sr1| sr1.rx_ne().bit_is_set()).await?,
+            }
+            *byte = self.i2c.dr().read().bits() as u8;
+        }
+
+        if len == 2 {
+            self.i2c.cr1().modify(|_, w| w.pos().clear_bit());
+        }
+
+        Ok(())
+    }
+
+    async fn transaction_inner(
+        &mut self,
+        addr: Address,
+        mut ops: &mut [Operation<'_>],
+    ) -> Result<(), Error> {
+        while let Some(first) = ops.first() {
+            // Adjacent operations of the same type are done without repeated START
+            let read = matches!(first, Operation::Read(_));
+            let n = ops
+                .iter()
+                .position(|op| matches!(op, Operation::Read(_)) != read)
+                .unwrap_or(ops.len());
+            let (group, rest) = core::mem::take(&mut ops).split_at_mut(n);
+            ops = rest;
+            let last = ops.is_empty();
+
+            if read {
+                let len = group
+                    .iter()
+                    .map(|op| match op {
+                        Operation::Read(rb) => rb.len(),
+                        Operation::Write(_) => 0,
+                    })
+                    .sum();
+                if len == 0 {
+                    return Err(Error::Overrun);
+                }
+                self.start_async(addr, true).await?;
+                let buffer = group.iter_mut().flat_map(|op| {
+                    let rb: &mut [u8] = match op {
+                        Operation::Read(rb) => rb,
+                        Operation::Write(_) => &mut [],
+                    };
+                    rb.iter_mut()
+                });
+                self.read_bytes_async(buffer, len, last).await?;
+            } else {
+                self.start_async(addr, false).await?;
+                let bytes = group.iter().flat_map(|op| {
+                    let wb: &[u8] = match op {
+                        Operation::Write(wb) => wb,
+                        Operation::Read(_) => &[],
+                    };
+                    wb.iter().cloned()
+                });
+                self.write_bytes_async(bytes).await?;
+                if last {
+                    self.end_transfer(true);
+                }
+            }
+        }
+
+        // Fallthrough is success
+        Ok(())
+    }
+
+    /// Asynchronous version of [`transaction_slice`](Self::transaction_slice)
+    pub async fn transaction_async(
+        &mut self,
+        addr: impl Into<Address>,
+        ops_slice: &mut [Operation<'_>],
+    ) -> Result<(), Error> {
+        let result = self.transaction_inner(addr.into(), ops_slice).await;
+        if result.is_err() {
+            // Release the bus
+            self.i2c.cr1().modify(|_, w| w.stop().set_bit());
+        }
+        result
+    }
+
+    /// Asynchronous version of [`read`](Self::read)
+    pub async fn read_async(
+        &mut self,
+        addr: impl Into<Address>,
+        buffer: &mut [u8],
+    ) -> Result<(), Error> {
+        self.transaction_async(addr, &mut [Operation::Read(buffer)])
+            .await
+    }
+
+    /// Asynchronous version of [`write`](Self::write)
+    pub async fn write_async(
+        &mut self,
+        addr: impl Into<Address>,
+        bytes: &[u8],
+    ) -> Result<(), Error> {
+        self.transaction_async(addr, &mut [Operation::Write(bytes)])
+            .await
+    }
+
+    /// Asynchronous version of [`write_read`](Self::write_read)
+    pub async fn write_read_async(
+        &mut self,
+        addr: impl Into<Address>,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Error> {
+        self.transaction_async(
+            addr,
+            &mut [Operation::Write(bytes), Operation::Read(buffer)],
+        )
+        .await
+    }
+}
+
+impl<I2C: Instance> embedded_hal_async::i2c::I2c for I2c<I2C> {
+    async fn transaction(
+        &mut self,
+        addr: SevenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.transaction_async(addr, operations).await
+    }
+}
+
+impl<I2C: Instance> embedded_hal_async::i2c::I2c<TenBitAddress> for I2c<I2C> {
+    async fn transaction(
+        &mut self,
+        addr: TenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.transaction_async(addr, operations).await
+    }
+}
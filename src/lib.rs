@@ -87,6 +87,7 @@ pub use crate::pac::interrupt;
 
 pub mod adc;
 pub mod bb;
+pub mod boot;
 #[cfg(all(feature = "can", any(feature = "can1", feature = "can2")))]
 pub mod can;
 pub mod crc32;
@@ -118,6 +119,7 @@ pub mod fsmc_lcd;
 #[cfg(all(feature = "dma2d", feature = "ltdc"))]
 pub mod ltdc;
 pub mod prelude;
+pub mod pwr;
 pub mod qei;
 #[cfg(feature = "quadspi")]
 pub mod qspi;
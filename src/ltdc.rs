@@ -2,13 +2,10 @@
 //!
 //! <div class="warning">Not tested yet</div>
 
-#[cfg_attr(test, allow(unused_imports))]
-use micromath::F32Ext;
-
 use crate::{
     gpio::{alt::ltdc as alt, PinSpeed, Speed},
-    pac::{DMA2D, LTDC, RCC},
-    rcc::{Enable, Reset},
+    pac::{DMA2D, LTDC},
+    rcc::{Clocks, Enable, Reset},
 };
 use fugit::HertzU32 as Hertz;
 
@@ -33,6 +30,22 @@ pub struct DisplayConfig {
     pub pixel_clock_pol: bool,
 }
 
+impl DisplayConfig {
+    /// Returns the pixel clock needed for `frame_rate`
+    pub const fn pixel_clock(&self) -> Hertz {
+        let (total_width, total_height) = self.total_size();
+        Hertz::from_raw(total_width as u32 * total_height as u32 * self.frame_rate as u32)
+    }
+
+    /// Returns the total width and height minus 1, including sync and porches
+    const fn total_size(&self) -> (u16, u16) {
+        (
+            self.h_sync + self.h_back_porch + self.active_width + self.h_front_porch - 1,
+            self.v_sync + self.v_back_porch + self.active_height + self.v_front_porch - 1,
+        )
+    }
+}
+
 /// Accessible layers
 /// * `L1`: layer 1
 /// * `L2`: layer 2
@@ -207,21 +220,24 @@ pub struct DisplayController<T: 'static + SupportedWord> {
 
 impl<T: 'static + SupportedWord> DisplayController<T> {
     /// Create and configure the DisplayController
+    ///
+    /// The pixel clock must be configured with [`CFGR::ltdc_clk`](crate::rcc::CFGR::ltdc_clk),
+    /// usually to [`DisplayConfig::pixel_clock`].
     pub fn new(
         ltdc: LTDC,
         dma2d: DMA2D,
         _pins: Option<LtdcPins>,
         pixel_format: PixelFormat,
         config: DisplayConfig,
-        hse: Option<Hertz>,
+        clocks: &Clocks,
     ) -> DisplayController<T> {
+        assert!(
+            clocks.ltdc_clk().is_some(),
+            "LTDC clock is not enabled, see CFGR::ltdc_clk"
+        );
+
         // Screen constants
-        let total_width: u16 =
-            config.h_sync + config.h_back_porch + config.active_width + config.h_front_porch - 1;
-        let total_height: u16 =
-            config.v_sync + config.v_back_porch + config.active_height + config.v_front_porch - 1;
-        let lcd_clk: u32 =
-            (total_width as u32) * (total_height as u32) * (config.frame_rate as u32);
+        let (total_width, total_height) = config.total_size();
 
         // TODO : change it to something safe ...
         unsafe {
@@ -236,70 +252,6 @@ impl<T: 'static + SupportedWord> DisplayController<T> {
             DMA2D::reset_unchecked();
         }
 
-        // Get base clock and PLLM divisor
-        let base_clk = if let Some(hse) = &hse {
-            hse.raw()
-        } else {
-            // If no HSE is provided, we use the HSI clock at 16 MHz
-            16_000_000
-        };
-        let rcc = unsafe { &(*RCC::ptr()) };
-        let pllm: u8 = rcc.pllcfgr().read().pllm().bits();
-
-        // There are 24 combinations possible for a divisor with PLLR and DIVR
-        // We find the one that is the closest possible to the target value
-        // while respecting all the conditions
-        let vco_in_mhz: f32 = (base_clk as f32 / pllm as f32) / 1_000_000.0;
-        let lcd_clk_mhz = (lcd_clk as f32) / 1_000_000.0;
-        let allowed_pllr = [2.0, 3.0, 4.0, 5.0, 6.0, 7.0];
-        let allowed_divr = [2.0, 4.0, 8.0, 16.0];
-        let mut best_pllr: f32 = allowed_pllr[0];
-        let mut best_divr: f32 = allowed_divr[0];
-        let mut best_plln: f32 = 100.0;
-        let mut best_error: f32 = (vco_in_mhz * best_plln) / (best_pllr * best_divr);
-        let mut error: f32;
-        let mut plln: f32;
-
-        for pllr in &allowed_pllr {
-            for divr in &allowed_divr {
-                plln = ((lcd_clk_mhz * divr * pllr) / vco_in_mhz).floor();
-                error = lcd_clk_mhz - (vco_in_mhz * plln) / (pllr * divr);
-
-                // We have to make sure that the VCO_OUT is in range [100, 432]
-                // MHz Because VCO_IN is in range [1, 2] Mhz, the condition
-                // PLLN in range [50, 432] is automatically satisfied
-                if 100.0 <= vco_in_mhz * plln
-                    && vco_in_mhz * plln <= 432.0
-                    && error >= 0.0
-                    && error < best_error
-                {
-                    best_pllr = *pllr;
-                    best_divr = *divr;
-                    best_plln = plln;
-                    best_error = error;
-                }
-            }
-        }
-
-        let pllsaidivr: u8 = match best_divr as u16 {
-            2 => 0b00,
-            4 => 0b01,
-            8 => 0b10,
-            16 => 0b11,
-            _ => unreachable!(),
-        };
-
-        // // Write PPLSAI configuration
-        rcc.pllsaicfgr().write(|w| unsafe {
-            w.pllsain().bits(best_plln as u16);
-            w.pllsair().bits(best_pllr as u8)
-        });
-        rcc.dckcfgr().modify(|_, w| w.pllsaidivr().set(pllsaidivr));
-
-        // Enable PLLSAI and wait for it
-        rcc.cr().modify(|_, w| w.pllsaion().on());
-        while rcc.cr().read().pllsairdy().is_not_ready() {}
-
         // Configure LTDC Timing registers
         ltdc.sscr().write(|w| {
             w.hsw().set(config.h_sync - 1);
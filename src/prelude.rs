@@ -66,6 +66,7 @@ pub use crate::i2c::dma::I2CMasterWriteDMA as _stm32f4xx_hal_i2c_dma_I2CMasterWr
 pub use crate::i2c::dma::I2CMasterWriteReadDMA as _stm32f4xx_hal_i2c_dma_I2CMasterWriteReadDMA;
 pub use crate::i2c::I2cExt as _stm32f4xx_hal_i2c_I2cExt;
 pub use crate::i2s::I2sExt as _stm32f4xx_hal_i2s_I2sExt;
+pub use crate::pwr::PwrExt as _stm32f4xx_hal_pwr_PwrExt;
 pub use crate::qei::QeiExt as _stm32f4xx_hal_QeiExt;
 pub use crate::rcc::RccExt as _stm32f4xx_hal_rcc_RccExt;
 #[cfg(feature = "rng")]
@@ -0,0 +1,535 @@
+//! Power control: low-power modes, programmable voltage detector and backup SRAM
+//!
+//! Three low-power modes are available:
+//! - Sleep: only the core is stopped, any interrupt or event wakes it up.
+//! - Stop: all clocks in the 1.2 V domain are stopped, SRAM and registers are preserved.
+//!   Wakeup is done by any EXTI line (pins, RTC alarm/wakeup, PVD...).
+//!   After wakeup the HSI is the system clock, [`Pwr::stop`] restores the clock tree.
+//! - Standby: 1.2 V domain is powered off, only backup domain is preserved.
+//!   Wakeup causes reset, it is done by WKUP pin, RTC alarm/wakeup/tamper/timestamp or NRST.
+//!
+//! ```
+//! use stm32f4xx_hal::{pac, prelude::*, pwr::StopMode, rtc::Rtc};
+//!
+//! let dp = pac::Peripherals::take().unwrap();
+//! let mut cp = cortex_m::Peripherals::take().unwrap();
+//! let clocks = dp.RCC.constrain().cfgr.use_hse(8.MHz()).sysclk(84.MHz()).freeze();
+//! let mut pwr = dp.PWR.constrain();
+//! // Drivers taking the raw peripheral borrow it from `Pwr`
+//! let mut rtc = Rtc::new(dp.RTC, &mut pwr);
+//!
+//! // Wait for EXTI interrupt in Stop mode with low-power regulator
+//! pwr.stop(&mut cp.SCB, StopMode::low_power().flash_power_down(true), &clocks);
+//! ```
+//!
+//! The programmable voltage detector (PVD) compares VDD with a selected threshold
+//! and signals crossings on EXTI line 16 (`PVD` interrupt):
+//!
+//! ```
+//! use core::cell::RefCell;
+//! use cortex_m::interrupt::Mutex;
+//! use stm32f4xx_hal::{
+//!     pac::{self, interrupt},
+//!     prelude::*,
+//!     pwr::{PvdEvent, PvdLevel, Pwr},
+//! };
+//!
+//! static PWR: Mutex<RefCell<Option<Pwr>>> = Mutex::new(RefCell::new(None));
+//!
+//! let mut dp = pac::Peripherals::take().unwrap();
+//! let mut pwr = dp.PWR.constrain();
+//! pwr.enable_pvd(PvdLevel::V2_8);
+//! pwr.listen_pvd(&mut dp.EXTI, PvdEvent::Rising);
+//! cortex_m::interrupt::free(|cs| PWR.borrow(cs).replace(Some(pwr)));
+//! unsafe { cortex_m::peripheral::NVIC::unmask(pac::Interrupt::PVD) };
+//!
+//! #[interrupt]
+//! fn PVD() {
+//!     cortex_m::interrupt::free(|cs| {
+//!         if let Some(pwr) = PWR.borrow(cs).borrow_mut().as_mut() {
+//!             // VDD dropped below 2.8 V
+//!             pwr.clear_pvd_interrupt();
+//!         }
+//!     });
+//! }
+//! ```
+
+use core::ops::{Deref, DerefMut};
+
+use cortex_m::peripheral::SCB;
+use enumflags2::BitFlags;
+
+use crate::pac::{EXTI, PWR, RCC};
+use crate::rcc::{Clocks, Enable};
+
+#[cfg(any(
+    feature = "gpio-f417",
+    feature = "gpio-f427",
+    feature = "gpio-f446",
+    feature = "gpio-f469"
+))]
+pub mod backup_sram;
+#[cfg(any(
+    feature = "gpio-f417",
+    feature = "gpio-f427",
+    feature = "gpio-f446",
+    feature = "gpio-f469"
+))]
+pub use backup_sram::BackupSram;
+
+/// PWR status flags
+#[enumflags2::bitflags]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[repr(u32)]
+pub enum Flag {
+    /// Wakeup event was received from WKUP pin or RTC
+    WakeUp = 1 << 0,
+    /// Device was in Standby mode
+    Standby = 1 << 1,
+    /// VDD is lower than the PVD threshold
+    PvdOutput = 1 << 2,
+}
+
+/// PWR clearable flags
+#[enumflags2::bitflags]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[repr(u32)]
+pub enum CFlag {
+    /// Wakeup event was received from WKUP pin or RTC
+    WakeUp = 1 << 0,
+    /// Device was in Standby mode
+    Standby = 1 << 1,
+}
+
+/// PVD events signalled on EXTI line 16
+#[enumflags2::bitflags]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[repr(u32)]
+pub enum PvdEvent {
+    /// VDD dropped below the PVD threshold
+    Rising = 1 << 0,
+    /// VDD rose above the PVD threshold
+    Falling = 1 << 1,
+}
+
+/// PVD threshold, typical values for falling VDD
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PvdLevel {
+    /// 2.0 V
+    #[default]
+    V2_0 = 0,
+    /// 2.1 V
+    V2_1 = 1,
+    /// 2.3 V
+    V2_3 = 2,
+    /// 2.5 V
+    V2_5 = 3,
+    /// 2.6 V
+    V2_6 = 4,
+    /// 2.7 V
+    V2_7 = 5,
+    /// 2.8 V
+    V2_8 = 6,
+    /// 2.9 V
+    V2_9 = 7,
+}
+
+/// Instruction used to enter low-power mode
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SleepEntry {
+    /// Wait For Interrupt
+    #[default]
+    Wfi,
+    /// Wait For Event
+    Wfe,
+}
+
+/// Regulator mode in Stop mode
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Regulator {
+    /// Main regulator is on, fastest wakeup
+    #[default]
+    Main,
+    /// Low-power regulator is on, lower consumption but longer wakeup
+    LowPower,
+}
+
+/// Stop mode configuration
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StopMode {
+    regulator: Regulator,
+    flash_power_down: bool,
+    #[cfg(any(feature = "gpio-f427", feature = "gpio-f446", feature = "gpio-f469"))]
+    under_drive: bool,
+    #[cfg(any(feature = "gpio-f410", feature = "gpio-f412", feature = "gpio-f413"))]
+    low_voltage: bool,
+    entry: SleepEntry,
+}
+
+impl StopMode {
+    /// Stop mode with main regulator on
+    pub fn main() -> Self {
+        Self::default()
+    }
+
+    /// Stop mode with low-power regulator
+    pub fn low_power() -> Self {
+        Self {
+            regulator: Regulator::LowPower,
+            ..Self::default()
+        }
+    }
+
+    /// Powers down the flash memory in Stop mode. Wakeup time is increased.
+    pub fn flash_power_down(mut self, enable: bool) -> Self {
+        self.flash_power_down = enable;
+        self
+    }
+
+    /// Puts the regulator in under-drive mode in Stop mode, further reducing consumption.
+    ///
+    /// Wakeup time is increased.
+    #[cfg(any(feature = "gpio-f427", feature = "gpio-f446", feature = "gpio-f469"))]
+    pub fn under_drive(mut self, enable: bool) -> Self {
+        self.under_drive = enable;
+        self
+    }
+
+    /// Puts the regulator in low voltage mode in Stop mode, further reducing consumption.
+    ///
+    /// Wakeup time is increased.
+    #[cfg(any(feature = "gpio-f410", feature = "gpio-f412", feature = "gpio-f413"))]
+    pub fn low_voltage(mut self, enable: bool) -> Self {
+        self.low_voltage = enable;
+        self
+    }
+
+    /// Instruction used to enter Stop mode,
+    /// only events wake up the core if [`SleepEntry::Wfe`] is used
+    pub fn entry(mut self, entry: SleepEntry) -> Self {
+        self.entry = entry;
+        self
+    }
+}
+
+/// Pin waking up the device from Standby mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WakeupPin {
+    /// WKUP pin (PA0)
+    #[cfg(not(any(
+        feature = "gpio-f410",
+        feature = "gpio-f412",
+        feature = "gpio-f413",
+        feature = "gpio-f446"
+    )))]
+    Wkup,
+    /// WKUP1 pin (PA0)
+    #[cfg(any(
+        feature = "gpio-f410",
+        feature = "gpio-f412",
+        feature = "gpio-f413",
+        feature = "gpio-f446"
+    ))]
+    Wkup1,
+    /// WKUP2 pin (PC0 on STM32F410/F412/F413, PC13 on STM32F446)
+    #[cfg(any(
+        feature = "gpio-f410",
+        feature = "gpio-f412",
+        feature = "gpio-f413",
+        feature = "gpio-f446"
+    ))]
+    Wkup2,
+    /// WKUP3 pin (PC1)
+    #[cfg(any(feature = "gpio-f410", feature = "gpio-f412", feature = "gpio-f413"))]
+    Wkup3,
+}
+
+/// Extension trait that constrains the `PWR` peripheral
+pub trait PwrExt {
+    /// Enables the `PWR` clock and constrains the peripheral
+    fn constrain(self) -> Pwr;
+}
+
+impl PwrExt for PWR {
+    fn constrain(self) -> Pwr {
+        unsafe {
+            PWR::enable_unchecked();
+        }
+        Pwr { rb: self }
+    }
+}
+
+/// Constrained PWR peripheral
+///
+/// Dereferences to `PWR`, so it can be passed to drivers taking `&mut PWR` like
+/// [`Rtc::new`](crate::rtc::Rtc::new).
+pub struct Pwr {
+    rb: PWR,
+}
+
+impl Deref for Pwr {
+    type Target = PWR;
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        &self.rb
+    }
+}
+
+impl DerefMut for Pwr {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.rb
+    }
+}
+
+impl Pwr {
+    /// Releases the `PWR` peripheral
+    pub fn release(self) -> PWR {
+        self.rb
+    }
+
+    /// Enters Sleep mode
+    pub fn sleep(&mut self, scb: &mut SCB, entry: SleepEntry) {
+        scb.clear_sleepdeep();
+        enter(entry);
+    }
+
+    /// Enters Sleep mode when returning from the last interrupt handler if `enable` is `true`.
+    ///
+    /// Can be used by applications which only run in interrupt handlers.
+    pub fn sleep_on_exit(&mut self, scb: &mut SCB, enable: bool) {
+        if enable {
+            scb.set_sleeponexit();
+        } else {
+            scb.clear_sleeponexit();
+        }
+    }
+
+    /// Enters Stop mode and waits for wakeup.
+    ///
+    /// Oscillators, PLLs and system clock switch are restored after wakeup,
+    /// so `clocks` stay valid.
+    #[cfg_attr(
+        not(any(feature = "gpio-f427", feature = "gpio-f446", feature = "gpio-f469")),
+        allow(unused_variables)
+    )]
+    pub fn stop(&mut self, scb: &mut SCB, mode: StopMode, clocks: &Clocks) {
+        let rcc = unsafe { &*RCC::ptr() };
+        let cr = rcc.cr().read();
+        let sw = rcc.cfgr().read().sw().bits();
+
+        self.rb.cr().modify(|_, w| {
+            w.pdds().clear_bit();
+            w.lpds().bit(mode.regulator == Regulator::LowPower);
+            w.fpds().bit(mode.flash_power_down);
+            #[cfg(any(feature = "gpio-f427", feature = "gpio-f446"))]
+            {
+                w.lpuds()
+                    .bit(mode.under_drive && mode.regulator == Regulator::LowPower);
+                w.mruds()
+                    .bit(mode.under_drive && mode.regulator == Regulator::Main);
+            }
+            // Under-drive regulator bits, LPUDS/MRUDS in RM0386, are named LPLVDS/MRLVDS in the PAC
+            #[cfg(feature = "gpio-f469")]
+            {
+                w.lplvds()
+                    .bit(mode.under_drive && mode.regulator == Regulator::LowPower);
+                w.mrlvds()
+                    .bit(mode.under_drive && mode.regulator == Regulator::Main);
+            }
+            #[cfg(any(feature = "gpio-f427", feature = "gpio-f446", feature = "gpio-f469"))]
+            unsafe {
+                w.uden().bits(if mode.under_drive { 0b11 } else { 0 });
+            }
+            #[cfg(any(feature = "gpio-f410", feature = "gpio-f412", feature = "gpio-f413"))]
+            {
+                w.lplvds()
+                    .bit(mode.low_voltage && mode.regulator == Regulator::LowPower);
+                w.mrlvds()
+                    .bit(mode.low_voltage && mode.regulator == Regulator::Main);
+            }
+            w
+        });
+        scb.set_sleepdeep();
+        enter(mode.entry);
+        scb.clear_sleepdeep();
+
+        #[cfg(any(feature = "gpio-f427", feature = "gpio-f446", feature = "gpio-f469"))]
+        if self.rb.csr().read().udrdy().bits() != 0 {
+            // Clear under-drive ready flag
+            self.rb.csr().modify(|_, w| unsafe { w.udrdy().bits(0b11) });
+        }
+
+        // HSE and PLLs are disabled by hardware in Stop mode
+        if cr.hseon().bit_is_set() {
+            rcc.cr().modify(|_, w| w.hseon().set_bit());
+            while rcc.cr().read().hserdy().bit_is_clear() {}
+        }
+        if cr.pllon().bit_is_set() {
+            rcc.cr().modify(|_, w| w.pllon().set_bit());
+
+            // Over-drive mode is disabled when entering Stop mode
+            #[cfg(any(feature = "gpio-f427", feature = "gpio-f446", feature = "gpio-f469"))]
+            if clocks.hclk().raw() > 168_000_000 {
+                self.rb.cr().modify(|_, w| w.oden().set_bit());
+                while self.rb.csr().read().odrdy().bit_is_clear() {}
+                self.rb.cr().modify(|_, w| w.odswen().set_bit());
+                while self.rb.csr().read().odswrdy().bit_is_clear() {}
+            }
+
+            while rcc.cr().read().pllrdy().bit_is_clear() {}
+        }
+        #[cfg(not(feature = "gpio-f410"))]
+        if cr.plli2son().bit_is_set() {
+            rcc.cr().modify(|_, w| w.plli2son().set_bit());
+            while rcc.cr().read().plli2srdy().bit_is_clear() {}
+        }
+        #[cfg(feature = "sai")]
+        #[cfg(not(feature = "gpio-f413"))]
+        if cr.pllsaion().bit_is_set() {
+            rcc.cr().modify(|_, w| w.pllsaion().set_bit());
+            while rcc.cr().read().pllsairdy().bit_is_clear() {}
+        }
+
+        rcc.cfgr().modify(|_, w| unsafe { w.sw().bits(sw) });
+        while rcc.cfgr().read().sws().bits() != sw {}
+    }
+
+    /// Enables or disables wakeup from Standby mode by rising edge on `pin`
+    pub fn enable_wakeup_pin(&mut self, pin: WakeupPin, enable: bool) {
+        self.rb.csr().modify(|_, w| match pin {
+            #[cfg(not(any(
+                feature = "gpio-f410",
+                feature = "gpio-f412",
+                feature = "gpio-f413",
+                feature = "gpio-f446"
+            )))]
+            WakeupPin::Wkup => w.ewup().bit(enable),
+            #[cfg(any(
+                feature = "gpio-f410",
+                feature = "gpio-f412",
+                feature = "gpio-f413",
+                feature = "gpio-f446"
+            ))]
+            WakeupPin::Wkup1 => w.ewup1().bit(enable),
+            #[cfg(any(
+                feature = "gpio-f410",
+                feature = "gpio-f412",
+                feature = "gpio-f413",
+                feature = "gpio-f446"
+            ))]
+            WakeupPin::Wkup2 => w.ewup2().bit(enable),
+            #[cfg(any(feature = "gpio-f410", feature = "gpio-f412", feature = "gpio-f413"))]
+            WakeupPin::Wkup3 => w.ewup3().bit(enable),
+        });
+    }
+
+    /// Enters Standby mode.
+    ///
+    /// Wakeup flag is cleared before entering, the device is reset on wakeup.
+    pub fn standby(&mut self, scb: &mut SCB) -> ! {
+        self.rb.cr().modify(|_, w| {
+            w.pdds().set_bit();
+            w.cwuf().set_bit()
+        });
+        scb.set_sleepdeep();
+        loop {
+            enter(SleepEntry::Wfi);
+        }
+    }
+
+    /// Enables the PVD with threshold `level`
+    pub fn enable_pvd(&mut self, level: PvdLevel) {
+        self.rb
+            .cr()
+            .modify(|_, w| unsafe { w.pls().bits(level as u8) });
+        self.rb.cr().modify(|_, w| w.pvde().set_bit());
+    }
+
+    /// Disables the PVD
+    pub fn disable_pvd(&mut self) {
+        self.rb.cr().modify(|_, w| w.pvde().clear_bit());
+    }
+
+    /// Returns `true` if VDD is lower than the PVD threshold
+    pub fn pvd_output(&self) -> bool {
+        self.rb.csr().read().pvdo().bit_is_set()
+    }
+
+    /// Returns `true` if the PVD EXTI line is pending
+    pub fn is_pvd_pending(&self) -> bool {
+        unsafe { (*EXTI::ptr()).pr().read().pr16().bit_is_set() }
+    }
+
+    /// Clears the pending bit of the PVD EXTI line
+    pub fn clear_pvd_interrupt(&mut self) {
+        // NOTE(unsafe) write-one-to-clear only affects line 16
+        unsafe { (*EXTI::ptr()).pr().write(|w| w.pr16().clear_bit_by_one()) };
+    }
+
+    /// Start listening for PVD `event`
+    pub fn listen_pvd(&mut self, exti: &mut EXTI, event: impl Into<BitFlags<PvdEvent>>) {
+        // EXTI 16 = PVD output
+        let event = event.into();
+        if event.contains(PvdEvent::Rising) {
+            exti.rtsr().modify(|_, w| w.tr16().enabled());
+        }
+        if event.contains(PvdEvent::Falling) {
+            exti.ftsr().modify(|_, w| w.tr16().enabled());
+        }
+        if !event.is_empty() {
+            exti.imr().modify(|_, w| w.mr16().set_bit());
+        }
+    }
+
+    /// Stop listening for PVD `event`
+    pub fn unlisten_pvd(&mut self, exti: &mut EXTI, event: impl Into<BitFlags<PvdEvent>>) {
+        // See the note in listen_pvd() about EXTI
+        let event = event.into();
+        if event.contains(PvdEvent::Rising) {
+            exti.rtsr().modify(|_, w| w.tr16().disabled());
+        }
+        if event.contains(PvdEvent::Falling) {
+            exti.ftsr().modify(|_, w| w.tr16().disabled());
+        }
+        if exti.rtsr().read().tr16().bit_is_clear() && exti.ftsr().read().tr16().bit_is_clear() {
+            exti.imr().modify(|_, w| w.mr16().clear_bit());
+        }
+    }
+}
+
+fn enter(entry: SleepEntry) {
+    // Finish all memory accesses before entering low-power mode
+    cortex_m::asm::dsb();
+    match entry {
+        SleepEntry::Wfi => cortex_m::asm::wfi(),
+        SleepEntry::Wfe => cortex_m::asm::wfe(),
+    }
+}
+
+impl crate::ReadFlags for Pwr {
+    type Flag = Flag;
+
+    #[inline(always)]
+    fn flags(&self) -> BitFlags<Self::Flag> {
+        BitFlags::from_bits_truncate(self.rb.csr().read().bits())
+    }
+}
+
+impl crate::ClearFlags for Pwr {
+    type Flag = CFlag;
+
+    #[inline(always)]
+    fn clear_flags(&mut self, flags: impl Into<BitFlags<Self::Flag>>) {
+        let flags = flags.into();
+        self.rb.cr().modify(|_, w| {
+            w.cwuf().bit(flags.contains(CFlag::WakeUp));
+            w.csbf().bit(flags.contains(CFlag::Standby))
+        });
+    }
+}
@@ -0,0 +1,146 @@
+//! Backup SRAM (BKPSRAM)
+//!
+//! 4 KB of SRAM in the backup domain. Its content is kept in Standby mode, after
+//! system resets and, while the backup regulator is on, when only VBAT is supplied.
+//!
+//! ```
+//! use stm32f4xx_hal::{pac, prelude::*};
+//!
+//! let dp = pac::Peripherals::take().unwrap();
+//! let mut pwr = dp.PWR.constrain();
+//! let mut sram = pwr.backup_sram().unwrap();
+//!
+//! let boots = &mut sram.as_mut_slice()[0];
+//! *boots = boots.wrapping_add(1);
+//! ```
+
+use core::mem;
+use core::slice;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use embedded_storage::{ReadStorage, Storage};
+
+use super::Pwr;
+use crate::pac::{PWR, RCC};
+
+const BKPSRAM_ADDRESS: usize = 0x4002_4000;
+
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Backup SRAM access error
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Accessed range is outside of the backup SRAM
+    OutOfBounds,
+}
+
+/// Battery-backed SRAM
+pub struct BackupSram {
+    _private: (),
+}
+
+impl Pwr {
+    /// Enables the backup regulator and the backup SRAM clock.
+    ///
+    /// Returns `None` if the backup SRAM was already taken.
+    pub fn backup_sram(&mut self) -> Option<BackupSram> {
+        if TAKEN.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+
+        let rcc = unsafe { &*RCC::ptr() };
+        rcc.ahb1enr().modify(|_, w| w.bkpsramen().set_bit());
+        // Stall the pipeline to work around erratum 2.1.13 (DM00037591)
+        cortex_m::asm::dsb();
+
+        // Backup domain is write protected after reset
+        self.rb.cr().modify(|_, w| w.dbp().set_bit());
+        self.rb.csr().modify(|_, w| w.bre().set_bit());
+        while self.rb.csr().read().brr().bit_is_clear() {}
+
+        Some(BackupSram { _private: () })
+    }
+}
+
+impl BackupSram {
+    /// Size in bytes
+    pub const SIZE: usize = 4 * 1024;
+
+    /// Memory-mapped address
+    pub fn address(&self) -> usize {
+        BKPSRAM_ADDRESS
+    }
+
+    /// Returns the backup SRAM as a byte slice
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(BKPSRAM_ADDRESS as *const u8, Self::SIZE) }
+    }
+
+    /// Returns the backup SRAM as a mutable byte slice
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(BKPSRAM_ADDRESS as *mut u8, Self::SIZE) }
+    }
+
+    /// Returns the start of the backup SRAM as a reference to `T`
+    ///
+    /// # Safety
+    ///
+    /// Content of the backup SRAM is unknown after power-on,
+    /// any bit pattern must be a valid `T`.
+    pub unsafe fn as_mut<T>(&mut self) -> &mut T {
+        assert!(mem::size_of::<T>() <= Self::SIZE);
+        assert!(mem::align_of::<T>() <= 4);
+        &mut *(BKPSRAM_ADDRESS as *mut T)
+    }
+
+    /// Keeps the backup regulator on if `enable` is `true`,
+    /// so content is retained when only VBAT is supplied
+    pub fn retain_on_vbat(&mut self, enable: bool) {
+        let pwr = unsafe { &*PWR::ptr() };
+        pwr.csr().modify(|_, w| w.bre().bit(enable));
+        if enable {
+            while pwr.csr().read().brr().bit_is_clear() {}
+        }
+    }
+
+    /// Disables the backup regulator and the backup SRAM clock.
+    ///
+    /// Content is lost when only VBAT is supplied.
+    pub fn release(self, pwr: &mut Pwr) {
+        pwr.rb.csr().modify(|_, w| w.bre().clear_bit());
+        let rcc = unsafe { &*RCC::ptr() };
+        rcc.ahb1enr().modify(|_, w| w.bkpsramen().clear_bit());
+        TAKEN.store(false, Ordering::Release);
+    }
+
+    fn range(offset: u32, len: usize) -> Result<core::ops::Range<usize>, Error> {
+        let start = offset as usize;
+        match start.checked_add(len) {
+            Some(end) if end <= Self::SIZE => Ok(start..end),
+            _ => Err(Error::OutOfBounds),
+        }
+    }
+}
+
+impl ReadStorage for BackupSram {
+    type Error = Error;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        let range = Self::range(offset, bytes.len())?;
+        bytes.copy_from_slice(&self.as_slice()[range]);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        Self::SIZE
+    }
+}
+
+impl Storage for BackupSram {
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        let range = Self::range(offset, bytes.len())?;
+        self.as_mut_slice()[range].copy_from_slice(bytes);
+        Ok(())
+    }
+}
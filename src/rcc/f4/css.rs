@@ -0,0 +1,117 @@
+//! Clock Security System
+//!
+//! When enabled with [`CFGR::enable_css`], failure of the HSE clock switches the system
+//! clock to HSI, stops HSE and PLLs clocked by it, and raises the non-maskable interrupt:
+//!
+//! ```ignore
+//! static CFGR: Mutex<RefCell<Option<CFGR>>> = Mutex::new(RefCell::new(None));
+//! static CLOCKS: Mutex<Cell<Option<Clocks>>> = Mutex::new(Cell::new(None));
+//!
+//! #[exception]
+//! fn NonMaskableInt() {
+//!     cortex_m::interrupt::free(|cs| {
+//!         if rcc::on_css_nmi() {
+//!             // Run from HSI through PLL with the original frequency requests
+//!             let cfgr = CFGR.borrow(cs).borrow_mut().take().unwrap();
+//!             let clocks = cfgr
+//!                 .recover_from_css()
+//!                 .unwrap_or_else(|_| CLOCKS.borrow(cs).get().unwrap().after_css());
+//!             CLOCKS.borrow(cs).set(Some(clocks));
+//!         }
+//!     });
+//! }
+//! ```
+
+use super::{Clocks, Error, CFGR, HSI};
+use crate::pac::RCC;
+
+use fugit::RateExtU32;
+
+/// Clears the Clock Security System interrupt.
+///
+/// Must be called from the `NonMaskableInt` handler. Returns `true` if HSE failure was detected,
+/// the system then runs from HSI with the prescalers of [`Clocks`], see [`Clocks::after_css`].
+pub fn on_css_nmi() -> bool {
+    let rcc = unsafe { &*RCC::ptr() };
+    if rcc.cir().read().cssf().bit_is_set() {
+        rcc.cir().modify(|_, w| w.cssc().set_bit());
+        true
+    } else {
+        false
+    }
+}
+
+impl CFGR {
+    /// Enables the Clock Security System monitoring HSE.
+    ///
+    /// This function has no effect unless use_hse() is also called.
+    pub const fn enable_css(mut self) -> Self {
+        self.css = true;
+        self
+    }
+
+    /// Initialises the hardware from HSI after HSE failure, keeping the other requested frequencies.
+    ///
+    /// PLLs are stopped and configured again with HSI as their source, see [`CFGR::try_refreeze`].
+    /// If the requested frequencies can not be generated from HSI or a PLL does not become ready,
+    /// the error is returned and the system keeps running from HSI, see [`Clocks::after_css`].
+    pub fn recover_from_css(self) -> Result<Clocks, Error> {
+        Self {
+            hse: None,
+            hse_bypass: false,
+            css: false,
+            ..self
+        }
+        .try_refreeze()
+    }
+}
+
+impl Clocks {
+    /// Returns the clocks after the Clock Security System switched the system clock to HSI.
+    ///
+    /// Bus prescalers are kept. Clocks generated by PLLs are reported as missing.
+    pub fn after_css(&self) -> Clocks {
+        let ahb_div = self.sysclk.raw() / self.hclk.raw();
+        let hclk = HSI / ahb_div;
+
+        let mut clocks = *self;
+        clocks.sysclk = HSI.Hz();
+        clocks.hclk = hclk.Hz();
+        clocks.pclk1 = (hclk / (self.hclk.raw() / self.pclk1.raw())).Hz();
+        clocks.pclk2 = (hclk / (self.hclk.raw() / self.pclk2.raw())).Hz();
+        clocks.timclk1 = (clocks.pclk1.raw() * (self.timclk1.raw() / self.pclk1.raw())).Hz();
+        clocks.timclk2 = (clocks.pclk2.raw() * (self.timclk2.raw() / self.pclk2.raw())).Hz();
+        clocks.pll48clk = None;
+
+        #[cfg(not(feature = "rcc_i2s_apb"))]
+        {
+            clocks.i2s_clk = None;
+        }
+        #[cfg(feature = "rcc_i2s_apb")]
+        {
+            clocks.i2s_apb1_clk = None;
+            clocks.i2s_apb2_clk = None;
+        }
+        #[cfg(feature = "sai")]
+        #[cfg(not(feature = "sai2"))]
+        {
+            clocks.saia_clk = None;
+            clocks.saib_clk = None;
+        }
+        #[cfg(feature = "sai2")]
+        {
+            clocks.sai1_clk = None;
+            clocks.sai2_clk = None;
+        }
+        #[cfg(feature = "ltdc")]
+        {
+            clocks.ltdc_clk = None;
+        }
+        #[cfg(feature = "spdifrx")]
+        {
+            clocks.spdifrx_clk = None;
+        }
+
+        clocks
+    }
+}
@@ -0,0 +1,482 @@
+//! Peripheral kernel clock selection
+//!
+//! Some peripherals are clocked independently from their bus. Their sources are selected
+//! with `CFGR`, the resulting kernel clocks are reported by `Clocks`:
+//!
+//! ```
+//! let clocks = rcc
+//!     .cfgr
+//!     .use_hse(8.MHz())
+//!     .sysclk(96.MHz())
+//!     .fmpi2c1_clk_src(Fmpi2cClkSrc::Hsi)
+//!     .sdio_clk_src(SdioClkSrc::Sysclk)
+//!     .freeze();
+//! assert_eq!(clocks.fmpi2c1_clk(), 16.MHz());
+//! assert_eq!(clocks.sdio_clk(), Some(96.MHz()));
+//! ```
+//!
+//! LSI and LSE are not started here, see [`Rtc`](crate::rtc::Rtc).
+
+#[cfg(any(feature = "fmpi2c1", feature = "gpio-f469"))]
+use super::CFGR;
+use super::{Clocks, Hertz};
+#[cfg(feature = "fmpi2c1")]
+use fugit::RateExtU32;
+
+#[cfg(feature = "spdifrx")]
+use super::pll::{I2sPll, MainPll, PllSetup};
+
+/// 48 MHz clock (CK48M) source, used by OTG FS, SDIO and RNG
+#[cfg(any(
+    feature = "gpio-f412",
+    feature = "gpio-f413",
+    feature = "gpio-f446",
+    feature = "gpio-f469"
+))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Clk48Src {
+    /// Main PLL "Q" output
+    #[default]
+    Pll,
+    /// I2S PLL "Q" output
+    #[cfg(any(feature = "gpio-f412", feature = "gpio-f413"))]
+    Plli2s,
+    /// SAI PLL "P" output
+    #[cfg(any(feature = "gpio-f446", feature = "gpio-f469"))]
+    Pllsai,
+}
+
+/// SDIO clock source
+#[cfg(any(
+    feature = "gpio-f412",
+    feature = "gpio-f413",
+    feature = "gpio-f446",
+    feature = "gpio-f469"
+))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SdioClkSrc {
+    /// 48 MHz clock
+    #[default]
+    Clk48,
+    /// System clock
+    Sysclk,
+}
+
+/// FMPI2C1 clock source
+#[cfg(feature = "fmpi2c1")]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Fmpi2cClkSrc {
+    /// APB1 clock
+    #[default]
+    Apb1 = 0b00,
+    /// System clock
+    Sysclk = 0b01,
+    /// HSI clock
+    Hsi = 0b10,
+}
+
+/// LPTIM1 clock source
+#[cfg(feature = "lptim1")]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LptimClkSrc {
+    /// APB1 clock
+    #[default]
+    Apb1 = 0b00,
+    /// HSI clock
+    Hsi = 0b01,
+    /// LSI oscillator, must be running
+    Lsi = 0b10,
+    /// LSE oscillator, must be running
+    Lse = 0b11,
+}
+
+/// DFSDM kernel clock source
+#[cfg(feature = "dfsdm1")]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DfsdmClkSrc {
+    /// APB2 clock
+    #[default]
+    Apb2,
+    /// System clock
+    Sysclk,
+}
+
+/// DFSDM audio clock source
+#[cfg(feature = "dfsdm1")]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DfsdmAudioClkSrc {
+    /// I2S clock of the APB1 instances
+    #[default]
+    I2sApb1,
+    /// I2S clock of the APB2 instances
+    I2sApb2,
+}
+
+/// SPDIFRX clock source
+#[cfg(feature = "spdifrx")]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpdifrxClkSrc {
+    /// Main PLL "R" output
+    #[default]
+    Pll,
+    /// I2S PLL "P" output
+    Plli2s,
+}
+
+/// HDMI-CEC clock source
+#[cfg(feature = "gpio-f446")]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CecClkSrc {
+    /// LSE oscillator, must be running
+    #[default]
+    Lse,
+    /// HSI clock divided by 488
+    Hsi,
+}
+
+/// Selected kernel clock sources
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct KernelClocks {
+    #[cfg(any(
+        feature = "gpio-f412",
+        feature = "gpio-f413",
+        feature = "gpio-f446",
+        feature = "gpio-f469"
+    ))]
+    pub(super) clk48: Clk48Src,
+    #[cfg(any(
+        feature = "gpio-f412",
+        feature = "gpio-f413",
+        feature = "gpio-f446",
+        feature = "gpio-f469"
+    ))]
+    sdio: SdioClkSrc,
+    #[cfg(feature = "fmpi2c1")]
+    fmpi2c1: Fmpi2cClkSrc,
+    #[cfg(feature = "lptim1")]
+    lptim1: LptimClkSrc,
+    #[cfg(feature = "dfsdm1")]
+    dfsdm1: DfsdmClkSrc,
+    #[cfg(feature = "dfsdm1")]
+    dfsdm1_audio: DfsdmAudioClkSrc,
+    #[cfg(feature = "dfsdm2")]
+    dfsdm2_audio: DfsdmAudioClkSrc,
+    #[cfg(feature = "spdifrx")]
+    spdifrx: SpdifrxClkSrc,
+    #[cfg(feature = "gpio-f446")]
+    cec: CecClkSrc,
+}
+
+impl KernelClocks {
+    /// Reset state of the clock muxes
+    pub(super) const fn new() -> Self {
+        Self {
+            #[cfg(any(
+                feature = "gpio-f412",
+                feature = "gpio-f413",
+                feature = "gpio-f446",
+                feature = "gpio-f469"
+            ))]
+            clk48: Clk48Src::Pll,
+            #[cfg(any(
+                feature = "gpio-f412",
+                feature = "gpio-f413",
+                feature = "gpio-f446",
+                feature = "gpio-f469"
+            ))]
+            sdio: SdioClkSrc::Clk48,
+            #[cfg(feature = "fmpi2c1")]
+            fmpi2c1: Fmpi2cClkSrc::Apb1,
+            #[cfg(feature = "lptim1")]
+            lptim1: LptimClkSrc::Apb1,
+            #[cfg(feature = "dfsdm1")]
+            dfsdm1: DfsdmClkSrc::Apb2,
+            #[cfg(feature = "dfsdm1")]
+            dfsdm1_audio: DfsdmAudioClkSrc::I2sApb1,
+            #[cfg(feature = "dfsdm2")]
+            dfsdm2_audio: DfsdmAudioClkSrc::I2sApb1,
+            #[cfg(feature = "spdifrx")]
+            spdifrx: SpdifrxClkSrc::Pll,
+            #[cfg(feature = "gpio-f446")]
+            cec: CecClkSrc::Lse,
+        }
+    }
+
+    /// Returns `true` if the 48 MHz clock is generated by the main PLL
+    #[cfg(not(feature = "gpio-f410"))]
+    pub(super) const fn clk48_on_main_pll(&self) -> bool {
+        #[cfg(any(
+            feature = "gpio-f412",
+            feature = "gpio-f413",
+            feature = "gpio-f446",
+            feature = "gpio-f469"
+        ))]
+        let on_main_pll = matches!(self.clk48, Clk48Src::Pll);
+        #[cfg(not(any(
+            feature = "gpio-f412",
+            feature = "gpio-f413",
+            feature = "gpio-f446",
+            feature = "gpio-f469"
+        )))]
+        let on_main_pll = true;
+        on_main_pll
+    }
+
+    /// Selects the kernel clock sources
+    pub(super) fn setup(&self) {
+        #[cfg(any(
+            feature = "gpio-f410",
+            feature = "gpio-f412",
+            feature = "gpio-f413",
+            feature = "gpio-f446"
+        ))]
+        {
+            let rcc = unsafe { &*crate::pac::RCC::ptr() };
+            rcc.dckcfgr2().modify(|_, w| {
+                #[cfg(feature = "fmpi2c1")]
+                unsafe {
+                    w.fmpi2c1sel().bits(self.fmpi2c1 as u8);
+                }
+                #[cfg(feature = "lptim1")]
+                unsafe {
+                    w.lptim1sel().bits(self.lptim1 as u8);
+                }
+                #[cfg(any(feature = "gpio-f412", feature = "gpio-f413", feature = "gpio-f446"))]
+                {
+                    w.ck48msel().bit(self.clk48 != Clk48Src::Pll);
+                    w.sdiosel().bit(self.sdio == SdioClkSrc::Sysclk);
+                }
+                #[cfg(feature = "spdifrx")]
+                w.spdifrxsel().bit(self.spdifrx == SpdifrxClkSrc::Plli2s);
+                #[cfg(feature = "gpio-f446")]
+                w.cecsel().bit(self.cec == CecClkSrc::Hsi);
+                w
+            });
+        }
+
+        // STM32F469/479 have the 48 MHz and SDIO clock muxes in DCKCFGR
+        #[cfg(feature = "gpio-f469")]
+        {
+            let rcc = unsafe { &*crate::pac::RCC::ptr() };
+            rcc.dckcfgr().modify(|_, w| {
+                w.ck48msel().bit(self.clk48 != Clk48Src::Pll);
+                w.sdiosel().bit(self.sdio == SdioClkSrc::Sysclk)
+            });
+        }
+
+        #[cfg(feature = "dfsdm1")]
+        {
+            let rcc = unsafe { &*crate::pac::RCC::ptr() };
+            rcc.dckcfgr().modify(|_, w| {
+                w.ckdfsdm1sel().bit(self.dfsdm1 == DfsdmClkSrc::Sysclk);
+                w.ckdfsdm1asel()
+                    .bit(self.dfsdm1_audio == DfsdmAudioClkSrc::I2sApb2);
+                #[cfg(feature = "dfsdm2")]
+                w.ckdfsdm2asel()
+                    .bit(self.dfsdm2_audio == DfsdmAudioClkSrc::I2sApb2);
+                w
+            });
+        }
+    }
+}
+
+#[cfg(any(
+    feature = "gpio-f412",
+    feature = "gpio-f413",
+    feature = "gpio-f446",
+    feature = "gpio-f469"
+))]
+impl CFGR {
+    /// Selects the source of the 48 MHz clock.
+    ///
+    /// The selected PLL is started for it if it is not used otherwise.
+    pub const fn clk48_src(mut self, src: Clk48Src) -> Self {
+        self.kernel.clk48 = src;
+        self
+    }
+
+    /// Selects the SDIO clock source
+    pub const fn sdio_clk_src(mut self, src: SdioClkSrc) -> Self {
+        self.kernel.sdio = src;
+        self
+    }
+}
+
+#[cfg(feature = "fmpi2c1")]
+impl CFGR {
+    /// Selects the FMPI2C1 clock source
+    pub const fn fmpi2c1_clk_src(mut self, src: Fmpi2cClkSrc) -> Self {
+        self.kernel.fmpi2c1 = src;
+        self
+    }
+}
+
+#[cfg(feature = "lptim1")]
+impl CFGR {
+    /// Selects the LPTIM1 clock source
+    pub const fn lptim1_clk_src(mut self, src: LptimClkSrc) -> Self {
+        self.kernel.lptim1 = src;
+        self
+    }
+}
+
+#[cfg(feature = "dfsdm1")]
+impl CFGR {
+    /// Selects the DFSDM kernel clock source
+    pub const fn dfsdm1_clk_src(mut self, src: DfsdmClkSrc) -> Self {
+        self.kernel.dfsdm1 = src;
+        self
+    }
+
+    /// Selects the DFSDM1 audio clock source
+    pub const fn dfsdm1_audio_clk_src(mut self, src: DfsdmAudioClkSrc) -> Self {
+        self.kernel.dfsdm1_audio = src;
+        self
+    }
+}
+
+#[cfg(feature = "dfsdm2")]
+impl CFGR {
+    /// Selects the DFSDM2 audio clock source
+    pub const fn dfsdm2_audio_clk_src(mut self, src: DfsdmAudioClkSrc) -> Self {
+        self.kernel.dfsdm2_audio = src;
+        self
+    }
+}
+
+#[cfg(feature = "spdifrx")]
+impl CFGR {
+    /// Selects the SPDIFRX clock source
+    pub const fn spdifrx_clk_src(mut self, src: SpdifrxClkSrc) -> Self {
+        self.kernel.spdifrx = src;
+        self
+    }
+
+    /// Returns the SPDIFRX clock
+    pub(super) const fn spdifrx_clk(&self, plls: &PllSetup) -> Option<u32> {
+        let pllsrcclk = match self.hse {
+            Some(hse) => hse,
+            None => super::HSI,
+        };
+        match self.kernel.spdifrx {
+            // "R" divider keeps its reset value of 2 unless used for I2S
+            SpdifrxClkSrc::Pll => match plls.main_pll() {
+                MainPll::Used { m, n, r, .. } => Some(
+                    pllsrcclk / m * n
+                        / match r {
+                            Some(r) => r,
+                            None => 2,
+                        },
+                ),
+                MainPll::Unused => None,
+            },
+            // "P" divider keeps its reset value of 2
+            SpdifrxClkSrc::Plli2s => match plls.i2s_pll() {
+                I2sPll::Used { config, .. } => {
+                    Some(pllsrcclk / config.m as u32 * config.n as u32 / 2)
+                }
+                I2sPll::Unused => None,
+            },
+        }
+    }
+}
+
+#[cfg(feature = "gpio-f446")]
+impl CFGR {
+    /// Selects the HDMI-CEC clock source
+    pub const fn cec_clk_src(mut self, src: CecClkSrc) -> Self {
+        self.kernel.cec = src;
+        self
+    }
+}
+
+impl Clocks {
+    /// Returns the SDIO clock
+    #[cfg(feature = "sdio")]
+    pub fn sdio_clk(&self) -> Option<Hertz> {
+        #[cfg(any(
+            feature = "gpio-f412",
+            feature = "gpio-f413",
+            feature = "gpio-f446",
+            feature = "gpio-f469"
+        ))]
+        if self.kernel.sdio == SdioClkSrc::Sysclk {
+            return Some(self.sysclk);
+        }
+        self.pll48clk
+    }
+
+    /// Returns the FMPI2C1 clock
+    #[cfg(feature = "fmpi2c1")]
+    pub fn fmpi2c1_clk(&self) -> Hertz {
+        match self.kernel.fmpi2c1 {
+            Fmpi2cClkSrc::Apb1 => self.pclk1,
+            Fmpi2cClkSrc::Sysclk => self.sysclk,
+            Fmpi2cClkSrc::Hsi => super::HSI.Hz(),
+        }
+    }
+
+    /// Returns the LPTIM1 clock
+    #[cfg(feature = "lptim1")]
+    pub fn lptim1_clk(&self) -> Hertz {
+        match self.kernel.lptim1 {
+            LptimClkSrc::Apb1 => self.pclk1,
+            LptimClkSrc::Hsi => super::HSI.Hz(),
+            LptimClkSrc::Lsi => 32.kHz(),
+            LptimClkSrc::Lse => 32_768.Hz(),
+        }
+    }
+
+    /// Returns the DFSDM kernel clock
+    #[cfg(feature = "dfsdm1")]
+    pub fn dfsdm1_clk(&self) -> Hertz {
+        match self.kernel.dfsdm1 {
+            DfsdmClkSrc::Apb2 => self.pclk2,
+            DfsdmClkSrc::Sysclk => self.sysclk,
+        }
+    }
+
+    /// Returns the DFSDM1 audio clock
+    #[cfg(feature = "dfsdm1")]
+    pub fn dfsdm1_audio_clk(&self) -> Option<Hertz> {
+        self.dfsdm_audio_clk(self.kernel.dfsdm1_audio)
+    }
+
+    /// Returns the DFSDM2 audio clock
+    #[cfg(feature = "dfsdm2")]
+    pub fn dfsdm2_audio_clk(&self) -> Option<Hertz> {
+        self.dfsdm_audio_clk(self.kernel.dfsdm2_audio)
+    }
+
+    #[cfg(feature = "dfsdm1")]
+    fn dfsdm_audio_clk(&self, src: DfsdmAudioClkSrc) -> Option<Hertz> {
+        match src {
+            DfsdmAudioClkSrc::I2sApb1 => self.i2s_apb1_clk,
+            DfsdmAudioClkSrc::I2sApb2 => self.i2s_apb2_clk,
+        }
+    }
+
+    /// Returns the SPDIFRX clock
+    #[cfg(feature = "spdifrx")]
+    pub fn spdifrx_clk(&self) -> Option<Hertz> {
+        self.spdifrx_clk
+    }
+
+    /// Returns the HDMI-CEC clock
+    #[cfg(feature = "gpio-f446")]
+    pub fn cec_clk(&self) -> Hertz {
+        match self.kernel.cec {
+            CecClkSrc::Lse => 32_768.Hz(),
+            CecClkSrc::Hsi => (super::HSI / 488).Hz(),
+        }
+    }
+}
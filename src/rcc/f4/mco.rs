@@ -0,0 +1,223 @@
+//! Microcontroller clock outputs
+//!
+//! ```
+//! use stm32f4xx_hal::{
+//!     pac,
+//!     prelude::*,
+//!     rcc::{Mco, Mco1Source, McoPre},
+//! };
+//!
+//! let dp = pac::Peripherals::take().unwrap();
+//! let rcc = dp.RCC.constrain();
+//! let gpioa = dp.GPIOA.split();
+//! let clocks = rcc
+//!     .cfgr
+//!     .use_hse(8.MHz())
+//!     .sysclk(168.MHz())
+//!     .mco1(Mco1Source::Hse, McoPre::Div1)
+//!     .freeze();
+//! let mco1 = Mco::mco1(gpioa.pa8);
+//! assert_eq!(clocks.mco1(), Some(8.MHz()));
+//! ```
+
+use super::{Clocks, CFGR};
+use crate::gpio::alt::rcc as alt;
+use crate::gpio::{PinSpeed, Speed};
+use crate::pac::RCC;
+
+/// MCO1 clock source
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mco1Source {
+    /// HSI clock
+    Hsi = 0b00,
+    /// LSE oscillator
+    Lse = 0b01,
+    /// HSE oscillator clock
+    Hse = 0b10,
+    /// Main PLL clock
+    Pll = 0b11,
+}
+
+/// MCO2 clock source
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mco2Source {
+    /// System clock
+    Sysclk = 0b00,
+    /// PLLI2S clock
+    #[cfg(not(feature = "gpio-f410"))]
+    Plli2s = 0b01,
+    /// HSE oscillator clock
+    Hse = 0b10,
+    /// Main PLL clock
+    Pll = 0b11,
+}
+
+/// MCO prescaler
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum McoPre {
+    /// No division
+    #[default]
+    Div1 = 0b000,
+    /// Division by 2
+    Div2 = 0b100,
+    /// Division by 3
+    Div3 = 0b101,
+    /// Division by 4
+    Div4 = 0b110,
+    /// Division by 5
+    Div5 = 0b111,
+}
+
+impl McoPre {
+    const fn divisor(self) -> u32 {
+        match self {
+            Self::Div1 => 1,
+            Self::Div2 => 2,
+            Self::Div3 => 3,
+            Self::Div4 => 4,
+            Self::Div5 => 5,
+        }
+    }
+}
+
+impl CFGR {
+    /// Outputs `source` divided by `pre` on the MCO1 pin
+    ///
+    /// [`Mco1Source::Lse`] must be enabled in the backup domain before clocks are frozen.
+    pub const fn mco1(mut self, source: Mco1Source, pre: McoPre) -> Self {
+        self.mco1 = Some((source, pre));
+        self
+    }
+
+    /// Outputs `source` divided by `pre` on the MCO2 pin
+    pub const fn mco2(mut self, source: Mco2Source, pre: McoPre) -> Self {
+        self.mco2 = Some((source, pre));
+        self
+    }
+
+    /// Returns the MCO output frequencies
+    #[cfg_attr(feature = "gpio-f410", allow(unused_variables))]
+    pub(super) const fn mco_clocks(
+        &self,
+        sysclk: u32,
+        pllclk: Option<u32>,
+        plli2sclk: Option<u32>,
+    ) -> (Option<u32>, Option<u32>) {
+        let mco1 = match self.mco1 {
+            Some((source, pre)) => divide(
+                match source {
+                    Mco1Source::Hsi => Some(super::HSI),
+                    Mco1Source::Lse => Some(32_768),
+                    Mco1Source::Hse => self.hse,
+                    Mco1Source::Pll => pllclk,
+                },
+                pre,
+            ),
+            None => None,
+        };
+
+        let mco2 = match self.mco2 {
+            Some((source, pre)) => divide(
+                match source {
+                    Mco2Source::Sysclk => Some(sysclk),
+                    #[cfg(not(feature = "gpio-f410"))]
+                    Mco2Source::Plli2s => plli2sclk,
+                    Mco2Source::Hse => self.hse,
+                    Mco2Source::Pll => pllclk,
+                },
+                pre,
+            ),
+            None => None,
+        };
+
+        (mco1, mco2)
+    }
+
+    /// Selects MCO sources and prescalers
+    pub(super) fn mco_setup(&self) {
+        let rcc = unsafe { &*RCC::ptr() };
+
+        if let Some((source, pre)) = self.mco1 {
+            rcc.cfgr().modify(|_, w| unsafe {
+                w.mco1().bits(source as u8);
+                w.mco1pre().bits(pre as u8)
+            });
+        }
+
+        if let Some((source, pre)) = self.mco2 {
+            rcc.cfgr().modify(|_, w| unsafe {
+                w.mco2().bits(source as u8);
+                w.mco2pre().bits(pre as u8)
+            });
+        }
+    }
+
+    /// Reports no MCO1 output if it is sourced from LSE which is not running.
+    ///
+    /// LSE is enabled in the backup domain, independently from `CFGR`.
+    pub(super) fn mco_check(&self, mut clocks: Clocks) -> Clocks {
+        let rcc = unsafe { &*RCC::ptr() };
+
+        if let Some((Mco1Source::Lse, _)) = self.mco1 {
+            if rcc.bdcr().read().lserdy().bit_is_clear() {
+                clocks.mco1 = None;
+            }
+        }
+
+        clocks
+    }
+}
+
+const fn divide(freq: Option<u32>, pre: McoPre) -> Option<u32> {
+    match freq {
+        Some(freq) => Some(freq / pre.divisor()),
+        None => None,
+    }
+}
+
+impl Clocks {
+    /// Returns the frequency of the MCO1 output
+    ///
+    /// Returns `None` if MCO1 is sourced from LSE which was not ready when clocks were frozen.
+    pub fn mco1(&self) -> Option<super::Hertz> {
+        self.mco1
+    }
+
+    /// Returns the frequency of the MCO2 output
+    pub fn mco2(&self) -> Option<super::Hertz> {
+        self.mco2
+    }
+}
+
+/// Clock output pin
+pub struct Mco<PIN> {
+    pin: PIN,
+}
+
+impl Mco<alt::Mco1> {
+    /// Outputs the clock selected by [`CFGR::mco1`] on `pin`
+    pub fn mco1(pin: impl Into<alt::Mco1>) -> Self {
+        let mut pin = pin.into();
+        pin.set_speed(Speed::VeryHigh);
+        Self { pin }
+    }
+}
+
+impl Mco<alt::Mco2> {
+    /// Outputs the clock selected by [`CFGR::mco2`] on `pin`
+    pub fn mco2(pin: impl Into<alt::Mco2>) -> Self {
+        let mut pin = pin.into();
+        pin.set_speed(Speed::VeryHigh);
+        Self { pin }
+    }
+}
+
+impl<PIN> Mco<PIN> {
+    /// Releases the pin
+    pub fn release(self) -> PIN {
+        self.pin
+    }
+}
@@ -1,12 +1,50 @@
-use crate::pac::rcc::cfgr::{HPRE, SW};
+use crate::pac::rcc::cfgr::SW;
 use crate::pac::{self, rcc, RCC};
 
 use super::{BusClock, BusTimerClock, RccBus};
 
+use enumflags2::BitFlags;
 use fugit::HertzU32 as Hertz;
-use fugit::RateExtU32;
 
-mod pll;
+/// `?` for results in `const fn`
+macro_rules! const_try {
+    ($e:expr) => {
+        match $e {
+            Ok(v) => v,
+            Err(e) => return Err(e),
+        }
+    };
+}
+
+pub mod pll;
+
+mod css;
+pub use css::on_css_nmi;
+
+mod mco;
+pub use mco::{Mco, Mco1Source, Mco2Source, McoPre};
+
+mod kernel;
+#[cfg(feature = "gpio-f446")]
+pub use kernel::CecClkSrc;
+#[cfg(feature = "fmpi2c1")]
+pub use kernel::Fmpi2cClkSrc;
+#[cfg(feature = "lptim1")]
+pub use kernel::LptimClkSrc;
+#[cfg(feature = "spdifrx")]
+pub use kernel::SpdifrxClkSrc;
+#[cfg(any(
+    feature = "gpio-f412",
+    feature = "gpio-f413",
+    feature = "gpio-f446",
+    feature = "gpio-f469"
+))]
+pub use kernel::{Clk48Src, SdioClkSrc};
+#[cfg(feature = "dfsdm1")]
+pub use kernel::{DfsdmAudioClkSrc, DfsdmClkSrc};
+
+#[cfg(test)]
+mod tests;
 
 mod enable;
 use crate::pac::rcc::RegisterBlock as RccRB;
@@ -207,28 +245,8 @@ impl BusTimerClock for APB2 {
 impl RccExt for RCC {
     fn constrain(self) -> Rcc {
         Rcc {
-            cfgr: CFGR {
-                hse: None,
-                hse_bypass: false,
-                hclk: None,
-                pclk1: None,
-                pclk2: None,
-                sysclk: None,
-                pll48clk: false,
-                i2s_ckin: None,
-
-                #[cfg(not(feature = "rcc_i2s_apb"))]
-                i2s_clk: None,
-                #[cfg(feature = "rcc_i2s_apb")]
-                i2s_apb1_clk: None,
-                #[cfg(feature = "rcc_i2s_apb")]
-                i2s_apb2_clk: None,
-
-                #[cfg(feature = "sai")]
-                sai1_clk: None,
-                #[cfg(feature = "sai")]
-                sai2_clk: None,
-            },
+            reset_reason: BitFlags::from_bits_truncate(self.csr().read().bits()),
+            cfgr: CFGR::new(),
         }
     }
 }
@@ -236,6 +254,57 @@ impl RccExt for RCC {
 /// Constrained RCC peripheral
 pub struct Rcc {
     pub cfgr: CFGR,
+    reset_reason: BitFlags<ResetReason>,
+}
+
+/// Cause of the last reset, flags of `RCC_CSR`
+///
+/// Flags accumulate over resets until cleared with [`Rcc::clear_reset_reason`].
+/// Power-on reset also sets [`ResetReason::Pin`] and [`ResetReason::BrownOut`].
+#[enumflags2::bitflags]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum ResetReason {
+    /// Brown-out reset (BORRSTF)
+    BrownOut = 1 << 25,
+    /// Reset from NRST pin (PINRSTF)
+    Pin = 1 << 26,
+    /// Power-on/power-down reset (PORRSTF)
+    PowerOn = 1 << 27,
+    /// Software reset (SFTRSTF)
+    Software = 1 << 28,
+    /// Independent watchdog reset (IWDGRSTF)
+    IndependentWatchdog = 1 << 29,
+    /// Window watchdog reset (WWDGRSTF)
+    WindowWatchdog = 1 << 30,
+    /// Low-power management reset (LPWRRSTF)
+    LowPower = 1 << 31,
+}
+
+impl Rcc {
+    /// Returns reset flags read when `RCC` was constrained
+    ///
+    /// ```
+    /// let mut rcc = dp.RCC.constrain();
+    /// let reason = rcc.reset_reason();
+    /// rcc.clear_reset_reason();
+    /// if reason.contains(ResetReason::IndependentWatchdog) {
+    ///     // ...
+    /// }
+    /// let clocks = rcc.cfgr.freeze();
+    /// ```
+    pub fn reset_reason(&self) -> BitFlags<ResetReason> {
+        self.reset_reason
+    }
+
+    /// Clears reset flags in `RCC_CSR` (RMVF), so the next reset reports only its own cause.
+    ///
+    /// Value returned by [`Rcc::reset_reason`] is kept.
+    pub fn clear_reset_reason(&mut self) {
+        let rcc = unsafe { &*RCC::ptr() };
+        rcc.csr().modify(|_, w| w.rmvf().set_bit());
+    }
 }
 
 /// Built-in high speed clock frequency
@@ -302,34 +371,173 @@ pub const PCLK2_MAX: u32 = SYSCLK_MAX / 2;
 /// Maximum APB1 peripheral clock frequency
 pub const PCLK1_MAX: u32 = PCLK2_MAX / 2;
 
+/// Minimum PLL system clock output, VCO minimum divided by 8
+const PLL_SYSCLK_MIN: u32 = 12_500_000;
+
+/// Maximum PLL system clock output, VCO maximum divided by 2
+const PLL_SYSCLK_MAX: u32 = 216_000_000;
+
+/// Supply voltage range
+///
+/// Selects the flash wait states, see also [`flash::Parallelism`](crate::flash::Parallelism).
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VoltageRange {
+    /// 1.8 V to 2.1 V, prefetch is disabled
+    V1_8To2_1,
+    /// 2.1 V to 2.4 V
+    V2_1To2_4,
+    /// 2.4 V to 2.7 V
+    V2_4To2_7,
+    /// 2.7 V to 3.6 V
+    #[default]
+    V2_7To3_6,
+}
+
+impl VoltageRange {
+    /// Maximum HCLK frequency per flash wait state
+    const fn flash_latency_step(self) -> u32 {
+        #[cfg(any(
+            feature = "gpio-f401",
+            feature = "gpio-f410",
+            feature = "gpio-f411",
+            feature = "gpio-f412",
+        ))]
+        let steps = [16_000_000, 18_000_000, 24_000_000, 30_000_000];
+
+        #[cfg(feature = "gpio-f413")]
+        let steps = [16_000_000, 18_000_000, 20_000_000, 25_000_000];
+
+        #[cfg(any(
+            feature = "gpio-f417",
+            feature = "gpio-f427",
+            feature = "gpio-f446",
+            feature = "gpio-f469",
+        ))]
+        let steps = [20_000_000, 22_000_000, 24_000_000, 30_000_000];
+
+        steps[self as usize]
+    }
+}
+
+/// I2S clock source (I2SSRC, I2S1SRC or I2S2SRC)
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum I2sClkSrc {
+    /// I2S_CKIN if its frequency matches the requested I2S clock, the PLL otherwise
+    #[default]
+    Auto,
+    /// I2S PLL "R" output, main PLL "R" output on F410
+    Pll,
+    /// External clock on the I2S_CKIN pin, see [`CFGR::i2s_ckin`]
+    Ckin,
+    /// PLL source clock, HSE if used or HSI
+    #[cfg(any(feature = "gpio-f410", feature = "rcc_i2s_apb"))]
+    PllSrc,
+}
+
+/// Maximum flash wait states
+#[cfg(feature = "gpio-f417")]
+const FLASH_LATENCY_MAX: u32 = 7;
+#[cfg(not(feature = "gpio-f417"))]
+const FLASH_LATENCY_MAX: u32 = 15;
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct CFGR {
     hse: Option<u32>,
     hse_bypass: bool,
+    css: bool,
     hclk: Option<u32>,
     pclk1: Option<u32>,
     pclk2: Option<u32>,
     sysclk: Option<u32>,
     pll48clk: bool,
+    voltage_range: VoltageRange,
 
     i2s_ckin: Option<u32>,
 
     #[cfg(not(feature = "rcc_i2s_apb"))]
     i2s_clk: Option<u32>,
+    #[cfg(not(feature = "rcc_i2s_apb"))]
+    i2s_clk_src: I2sClkSrc,
     #[cfg(feature = "rcc_i2s_apb")]
     i2s_apb1_clk: Option<u32>,
     #[cfg(feature = "rcc_i2s_apb")]
+    i2s_apb1_clk_src: I2sClkSrc,
+    #[cfg(feature = "rcc_i2s_apb")]
     i2s_apb2_clk: Option<u32>,
+    #[cfg(feature = "rcc_i2s_apb")]
+    i2s_apb2_clk_src: I2sClkSrc,
 
     #[cfg(feature = "sai")]
     sai1_clk: Option<u32>,
     #[cfg(feature = "sai")]
     sai2_clk: Option<u32>,
+
+    #[cfg(feature = "ltdc")]
+    ltdc_clk: Option<u32>,
+
+    mco1: Option<(Mco1Source, McoPre)>,
+    mco2: Option<(Mco2Source, McoPre)>,
+
+    kernel: kernel::KernelClocks,
+}
+
+impl Default for CFGR {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CFGR {
+    /// Creates a configuration running from HSI without PLLs.
+    ///
+    /// Usually obtained from [`Rcc::cfgr`], use this to compute a [`ClockPlan`] on the host.
+    pub const fn new() -> Self {
+        Self {
+            hse: None,
+            hse_bypass: false,
+            css: false,
+            hclk: None,
+            pclk1: None,
+            pclk2: None,
+            sysclk: None,
+            pll48clk: false,
+            voltage_range: VoltageRange::V2_7To3_6,
+            i2s_ckin: None,
+
+            #[cfg(not(feature = "rcc_i2s_apb"))]
+            i2s_clk: None,
+            #[cfg(not(feature = "rcc_i2s_apb"))]
+            i2s_clk_src: I2sClkSrc::Auto,
+            #[cfg(feature = "rcc_i2s_apb")]
+            i2s_apb1_clk: None,
+            #[cfg(feature = "rcc_i2s_apb")]
+            i2s_apb1_clk_src: I2sClkSrc::Auto,
+            #[cfg(feature = "rcc_i2s_apb")]
+            i2s_apb2_clk: None,
+            #[cfg(feature = "rcc_i2s_apb")]
+            i2s_apb2_clk_src: I2sClkSrc::Auto,
+
+            #[cfg(feature = "sai")]
+            sai1_clk: None,
+            #[cfg(feature = "sai")]
+            sai2_clk: None,
+
+            #[cfg(feature = "ltdc")]
+            ltdc_clk: None,
+
+            mco1: None,
+            mco2: None,
+
+            kernel: kernel::KernelClocks::new(),
+        }
+    }
+
     /// Uses HSE (external oscillator) instead of HSI (internal RC oscillator) as the clock source.
     /// Will result in a hang if an external oscillator is not connected or it fails to start.
-    pub fn use_hse(mut self, freq: Hertz) -> Self {
+    pub const fn use_hse(mut self, freq: Hertz) -> Self {
         self.hse = Some(freq.raw());
         self
     }
@@ -341,34 +549,42 @@ impl CFGR {
     /// frequency specified in the call to use_hse(), and the OSC_OUT pin should not be connected.
     ///
     /// This function has no effect unless use_hse() is also called.
-    pub fn bypass_hse_oscillator(self) -> Self {
+    pub const fn bypass_hse_oscillator(self) -> Self {
         Self {
             hse_bypass: true,
             ..self
         }
     }
 
-    pub fn hclk(mut self, freq: Hertz) -> Self {
+    /// Declares the supply voltage range, 2.7 V to 3.6 V by default.
+    ///
+    /// Lower voltages need more flash wait states for the same HCLK frequency.
+    pub const fn voltage_range(mut self, range: VoltageRange) -> Self {
+        self.voltage_range = range;
+        self
+    }
+
+    pub const fn hclk(mut self, freq: Hertz) -> Self {
         self.hclk = Some(freq.raw());
         self
     }
 
-    pub fn pclk1(mut self, freq: Hertz) -> Self {
+    pub const fn pclk1(mut self, freq: Hertz) -> Self {
         self.pclk1 = Some(freq.raw());
         self
     }
 
-    pub fn pclk2(mut self, freq: Hertz) -> Self {
+    pub const fn pclk2(mut self, freq: Hertz) -> Self {
         self.pclk2 = Some(freq.raw());
         self
     }
 
-    pub fn sysclk(mut self, freq: Hertz) -> Self {
+    pub const fn sysclk(mut self, freq: Hertz) -> Self {
         self.sysclk = Some(freq.raw());
         self
     }
 
-    pub fn require_pll48clk(mut self) -> Self {
+    pub const fn require_pll48clk(mut self) -> Self {
         self.pll48clk = true;
         self
     }
@@ -376,8 +592,8 @@ impl CFGR {
     /// Declares that the selected frequency is available at the I2S clock input pin (I2S_CKIN).
     ///
     /// If this frequency matches the requested SAI or I2S frequencies, the external I2S clock is
-    /// used to generate the clocks.
-    pub fn i2s_ckin(mut self, freq: Hertz) -> Self {
+    /// used to generate the clocks, unless another [`I2sClkSrc`] is selected.
+    pub const fn i2s_ckin(mut self, freq: Hertz) -> Self {
         self.i2s_ckin = Some(freq.raw());
         self
     }
@@ -386,38 +602,65 @@ impl CFGR {
 #[cfg(not(feature = "rcc_i2s_apb"))]
 impl CFGR {
     /// Selects an I2S clock frequency and enables the I2S clock.
-    pub fn i2s_clk(mut self, freq: Hertz) -> Self {
+    pub const fn i2s_clk(mut self, freq: Hertz) -> Self {
         self.i2s_clk = Some(freq.raw());
         self
     }
+
+    /// Selects the I2S clock source.
+    ///
+    /// The frequency set with [`CFGR::i2s_clk`] is the target of the PLL, it is ignored for
+    /// other sources.
+    pub const fn i2s_clk_src(mut self, src: I2sClkSrc) -> Self {
+        self.i2s_clk_src = src;
+        self
+    }
 }
 
 #[cfg(feature = "rcc_i2s_apb")]
 impl CFGR {
     /// Selects an I2S clock frequency for the first set of I2S instancesand enables the I2S clock.
-    pub fn i2s_apb1_clk(mut self, freq: Hertz) -> Self {
+    pub const fn i2s_apb1_clk(mut self, freq: Hertz) -> Self {
         self.i2s_apb1_clk = Some(freq.raw());
         self
     }
 
     /// Selects an I2S clock frequency for the second set of I2S instances and enables the I2S clock.
-    pub fn i2s_apb2_clk(mut self, freq: Hertz) -> Self {
+    pub const fn i2s_apb2_clk(mut self, freq: Hertz) -> Self {
         self.i2s_apb2_clk = Some(freq.raw());
         self
     }
+
+    /// Selects the clock source of the first set of I2S instances.
+    ///
+    /// The frequency set with [`CFGR::i2s_apb1_clk`] is the target of the PLL, it is ignored
+    /// for other sources.
+    pub const fn i2s_apb1_clk_src(mut self, src: I2sClkSrc) -> Self {
+        self.i2s_apb1_clk_src = src;
+        self
+    }
+
+    /// Selects the clock source of the second set of I2S instances.
+    ///
+    /// The frequency set with [`CFGR::i2s_apb2_clk`] is the target of the PLL, it is ignored
+    /// for other sources.
+    pub const fn i2s_apb2_clk_src(mut self, src: I2sClkSrc) -> Self {
+        self.i2s_apb2_clk_src = src;
+        self
+    }
 }
 
 #[cfg(feature = "sai")]
 #[cfg(not(feature = "sai2"))]
 impl CFGR {
     /// Selects a SAIA clock frequency and enables the SAIA clock.
-    pub fn saia_clk(mut self, freq: Hertz) -> Self {
+    pub const fn saia_clk(mut self, freq: Hertz) -> Self {
         self.sai1_clk = Some(freq.raw());
         self
     }
 
     /// Selects a SAIB clock frequency and enables the SAIB clock.
-    pub fn saib_clk(mut self, freq: Hertz) -> Self {
+    pub const fn saib_clk(mut self, freq: Hertz) -> Self {
         self.sai2_clk = Some(freq.raw());
         self
     }
@@ -426,24 +669,36 @@ impl CFGR {
 #[cfg(feature = "sai2")]
 impl CFGR {
     /// Selects a SAI1 clock frequency and enables the SAI1 clock.
-    pub fn sai1_clk(mut self, freq: Hertz) -> Self {
+    pub const fn sai1_clk(mut self, freq: Hertz) -> Self {
         self.sai1_clk = Some(freq.raw());
         self
     }
 
     /// Selects a SAI2 clock frequency and enables the SAI2 clock.
-    pub fn sai2_clk(mut self, freq: Hertz) -> Self {
+    pub const fn sai2_clk(mut self, freq: Hertz) -> Self {
         self.sai2_clk = Some(freq.raw());
         self
     }
 }
 
+#[cfg(feature = "ltdc")]
+impl CFGR {
+    /// Selects the LTDC pixel clock frequency and enables the LTDC clock.
+    ///
+    /// The pixel clock is generated by PLLSAI together with the SAI clocks and, if selected
+    /// with `CFGR::clk48_src` on F469, the 48 MHz clock, see [`Error::PllsaiConflict`].
+    pub const fn ltdc_clk(mut self, freq: Hertz) -> Self {
+        self.ltdc_clk = Some(freq.raw());
+        self
+    }
+}
+
 #[cfg(feature = "sai")]
 impl CFGR {
-    fn sai_clocks(&self) -> SaiClocks {
-        let sai1_ext = self.sai1_clk.is_some() && self.sai1_clk == self.i2s_ckin;
+    const fn sai_clocks(&self) -> Result<SaiClocks, Error> {
+        let sai1_ext = same_clk(self.sai1_clk, self.i2s_ckin);
         #[cfg(not(feature = "sai2"))]
-        let sai2_ext = self.sai2_clk.is_some() && self.sai2_clk == self.i2s_ckin;
+        let sai2_ext = same_clk(self.sai2_clk, self.i2s_ckin);
         // Not the PLL output, but the target clock after the divider.
         let pll_sai_clk = if sai1_ext { None } else { self.sai1_clk };
         // The STM32F446 only supports I2S_CKIN for SAI1.
@@ -451,79 +706,76 @@ impl CFGR {
         let pll_sai_clk2 = self.sai2_clk;
         #[cfg(not(feature = "sai2"))]
         let pll_sai_clk2 = if sai2_ext { None } else { self.sai2_clk };
-        if pll_sai_clk.is_some() && pll_sai_clk2.is_some() && pll_sai_clk != pll_sai_clk2 {
-            panic!("only one SAI PLL frequency implemented");
+        // Only one SAI PLL frequency is implemented
+        if pll_sai_clk.is_some() && pll_sai_clk2.is_some() && !same_clk(pll_sai_clk, pll_sai_clk2) {
+            return Err(Error::PllsaiUnreachable);
         }
-        SaiClocks {
+        Ok(SaiClocks {
             sai1_ext,
             #[cfg(not(feature = "sai2"))]
             sai2_ext,
             pll_sai_clk,
-        }
+        })
     }
 }
 
 impl CFGR {
     #[cfg(feature = "rcc_i2s_apb")]
-    fn i2s_clocks(&self) -> I2sClocks {
-        let i2s_apb1_ext = self.i2s_apb1_clk.is_some() && self.i2s_apb1_clk == self.i2s_ckin;
-        let i2s_apb2_ext = self.i2s_apb2_clk.is_some() && self.i2s_apb2_clk == self.i2s_ckin;
-        let pll_i2s_clk = if i2s_apb1_ext {
-            None
-        } else {
-            self.i2s_apb1_clk
+    const fn i2s_clocks(&self) -> Result<I2sClocks, Error> {
+        let i2s_apb1_src = self.i2s_src(self.i2s_apb1_clk_src, self.i2s_apb1_clk);
+        let i2s_apb2_src = self.i2s_src(self.i2s_apb2_clk_src, self.i2s_apb2_clk);
+        let pll_i2s_clk = match i2s_apb1_src {
+            I2sClkSrc::Pll => self.i2s_apb1_clk,
+            _ => None,
         };
-        let pll_i2s_clk2 = if i2s_apb2_ext {
-            None
-        } else {
-            self.i2s_apb2_clk
+        let pll_i2s_clk2 = match i2s_apb2_src {
+            I2sClkSrc::Pll => self.i2s_apb2_clk,
+            _ => None,
         };
-        if pll_i2s_clk.is_some() && pll_i2s_clk2.is_some() && pll_i2s_clk != pll_i2s_clk2 {
-            panic!("only one I2S PLL frequency implemented");
+        // Only one I2S PLL frequency is implemented
+        if pll_i2s_clk.is_some() && pll_i2s_clk2.is_some() && !same_clk(pll_i2s_clk, pll_i2s_clk2) {
+            return Err(Error::Plli2sUnreachable);
         }
-        I2sClocks {
-            i2s_apb1_ext,
-            i2s_apb2_ext,
+        Ok(I2sClocks {
+            i2s_apb1_src,
+            i2s_apb2_src,
             pll_i2s_clk,
-        }
+        })
     }
 
     #[cfg(not(feature = "rcc_i2s_apb"))]
-    fn i2s_clocks(&self) -> I2sClocks {
-        let i2s_ext = self.i2s_clk.is_some() && self.i2s_clk == self.i2s_ckin;
-        let pll_i2s_clk = if i2s_ext { None } else { self.i2s_clk };
-        I2sClocks {
-            i2s_ext,
+    const fn i2s_clocks(&self) -> Result<I2sClocks, Error> {
+        let i2s_src = self.i2s_src(self.i2s_clk_src, self.i2s_clk);
+        let pll_i2s_clk = match i2s_src {
+            I2sClkSrc::Pll => self.i2s_clk,
+            _ => None,
+        };
+        Ok(I2sClocks {
+            i2s_src,
             pll_i2s_clk,
+        })
+    }
+
+    /// Resolves [`I2sClkSrc::Auto`] for the requested I2S clock
+    const fn i2s_src(&self, src: I2sClkSrc, i2s_clk: Option<u32>) -> I2sClkSrc {
+        match src {
+            I2sClkSrc::Auto if same_clk(i2s_clk, self.i2s_ckin) => I2sClkSrc::Ckin,
+            I2sClkSrc::Auto => I2sClkSrc::Pll,
+            src => src,
         }
     }
 }
 
 impl CFGR {
-    fn flash_setup(sysclk: u32) {
+    fn flash_setup(latency: u8, voltage_range: VoltageRange) {
         use crate::pac::FLASH;
 
-        #[cfg(any(
-            feature = "gpio-f401",
-            feature = "gpio-f417",
-            feature = "gpio-f410",
-            feature = "gpio-f411",
-            feature = "gpio-f412",
-            feature = "gpio-f427",
-            feature = "gpio-f446",
-            feature = "gpio-f469",
-        ))]
-        let flash_latency_step = 30_000_000;
-
-        #[cfg(feature = "gpio-f413")]
-        let flash_latency_step = 25_000_000;
-
         unsafe {
             let flash = &(*FLASH::ptr());
             // Adjust flash wait states
             flash.acr().modify(|_, w| {
-                w.latency().bits(((sysclk - 1) / flash_latency_step) as u8);
-                w.prften().set_bit();
+                w.latency().bits(latency);
+                w.prften().bit(voltage_range != VoltageRange::V1_8To2_1);
                 w.icen().set_bit();
                 w.dcen().set_bit()
             });
@@ -531,8 +783,17 @@ impl CFGR {
     }
 
     /// Initialises the hardware according to CFGR state returning a Clocks instance.
-    /// Panics if overclocking is attempted.
+    /// Panics if overclocking is attempted or the configuration fails, see [`CFGR::try_freeze`].
     pub fn freeze(self) -> Clocks {
+        self.freeze_internal(false).unwrap()
+    }
+
+    /// Initialises the hardware according to CFGR state returning a Clocks instance.
+    ///
+    /// Returns an error if the requested clocks can not be generated or an oscillator or PLL
+    /// does not become ready. The system clock is not switched in that case, so another
+    /// configuration (e.g. without HSE) can be tried.
+    pub fn try_freeze(self) -> Result<Clocks, Error> {
         self.freeze_internal(false)
     }
 
@@ -544,45 +805,109 @@ impl CFGR {
     /// This method does not check if the clocks are bigger or smaller than the officially
     /// recommended.
     pub unsafe fn freeze_unchecked(self) -> Clocks {
-        self.freeze_internal(true)
+        self.freeze_internal(true).unwrap()
     }
 
-    fn freeze_internal(self, unchecked: bool) -> Clocks {
-        let rcc = unsafe { &*RCC::ptr() };
+    fn freeze_internal(self, unchecked: bool) -> Result<Clocks, Error> {
+        self.solve_internal(unchecked)?.apply()
+    }
+
+    /// Switches the running system to this configuration, returning the new Clocks instance.
+    /// Panics if overclocking is attempted or the configuration fails, see [`CFGR::try_refreeze`].
+    ///
+    /// Drivers keep the frequencies of the previous `Clocks` until their `configure` method
+    /// is called with the new ones.
+    pub fn refreeze(self) -> Clocks {
+        self.try_refreeze().unwrap()
+    }
 
-        let pllsrcclk = self.hse.unwrap_or(HSI);
-        let sysclk = self.sysclk.unwrap_or(pllsrcclk);
+    /// Switches the running system to this configuration, returning the new Clocks instance.
+    ///
+    /// The system clock runs from HSI while oscillators and PLLs are reconfigured, so flash
+    /// wait states and over-drive are never below the needs of the running frequency.
+    /// All PLLs are stopped and restarted, so I2S, SAI, LTDC and 48 MHz clocks pause even if
+    /// their configuration is unchanged.
+    /// If an oscillator or PLL does not become ready, the system is left on HSI with the
+    /// previous bus prescalers, see [`Clocks::after_css`].
+    pub fn try_refreeze(self) -> Result<Clocks, Error> {
+        let plan = self.solve_internal(false)?;
+        plan.enter_hsi();
+        plan.apply()
+    }
+
+    /// Computes the clock tree without accessing hardware.
+    ///
+    /// Performs the same checks as [`CFGR::try_freeze`] apart from waiting for oscillators and
+    /// PLLs, so clock configurations can be validated on the host or at compile time.
+    pub const fn solve(&self) -> Result<ClockPlan, Error> {
+        self.solve_internal(false)
+    }
+
+    const fn solve_internal(&self, unchecked: bool) -> Result<ClockPlan, Error> {
+        let pllsrcclk = match self.hse {
+            Some(hse) => hse,
+            None => HSI,
+        };
+        let sysclk = match self.sysclk {
+            Some(sysclk) => sysclk,
+            None => pllsrcclk,
+        };
         let sysclk_on_pll = sysclk != pllsrcclk;
 
-        let plls = pll::PllSetup::from_cfgr(&self, pllsrcclk, sysclk_on_pll.then_some(sysclk));
+        // PLL input must be divided to 1-2 MHz, VCO output is 100-432 MHz divided by 2-8
+        if sysclk_on_pll
+            && (pllsrcclk < 1_000_000
+                || sysclk > PLL_SYSCLK_MAX
+                || !unchecked && sysclk < PLL_SYSCLK_MIN)
+        {
+            return Err(Error::SysclkUnreachable);
+        }
+
+        let plls = const_try!(pll::PllSetup::from_cfgr(
+            self,
+            pllsrcclk,
+            if sysclk_on_pll { Some(sysclk) } else { None },
+        ));
         let sysclk = if sysclk_on_pll {
-            plls.pllsysclk.unwrap()
+            match plls.pllsysclk {
+                Some(pllsysclk) => pllsysclk,
+                None => return Err(Error::SysclkUnreachable),
+            }
         } else {
             sysclk
         };
 
-        assert!(unchecked || !sysclk_on_pll || (SYSCLK_MIN..=SYSCLK_MAX).contains(&sysclk));
+        if !unchecked && sysclk_on_pll && (sysclk < SYSCLK_MIN || sysclk > SYSCLK_MAX) {
+            return Err(Error::SysclkOutOfRange);
+        }
+        if self.pll48clk && !is_pll48clk_valid(plls.pll48clk) {
+            return Err(Error::Pll48clkInvalid);
+        }
 
-        let hclk = self.hclk.unwrap_or(sysclk);
+        let hclk = match self.hclk {
+            Some(hclk) => hclk,
+            None => sysclk,
+        };
         let (hpre_bits, hpre_div) = match (sysclk + hclk - 1) / hclk {
             0 => unreachable!(),
-            1 => (HPRE::Div1, 1),
-            2 => (HPRE::Div2, 2),
-            3..=5 => (HPRE::Div4, 4),
-            6..=11 => (HPRE::Div8, 8),
-            12..=39 => (HPRE::Div16, 16),
-            40..=95 => (HPRE::Div64, 64),
-            96..=191 => (HPRE::Div128, 128),
-            192..=383 => (HPRE::Div256, 256),
-            _ => (HPRE::Div512, 512),
+            1 => (0b0000, 1),
+            2 => (0b1000, 2),
+            3..=5 => (0b1001, 4),
+            6..=11 => (0b1010, 8),
+            12..=39 => (0b1011, 16),
+            40..=95 => (0b1100, 64),
+            96..=191 => (0b1101, 128),
+            192..=383 => (0b1110, 256),
+            _ => (0b1111, 512),
         };
 
         // Calculate real AHB clock
         let hclk = sysclk / hpre_div;
 
-        let pclk1 = self
-            .pclk1
-            .unwrap_or_else(|| crate::min_u32(PCLK1_MAX, hclk));
+        let pclk1 = match self.pclk1 {
+            Some(pclk1) => pclk1,
+            None => crate::min_u32(PCLK1_MAX, hclk),
+        };
         let (ppre1_bits, ppre1) = match (hclk + pclk1 - 1) / pclk1 {
             0 => unreachable!(),
             1 => (0b000, 1u8),
@@ -593,13 +918,16 @@ impl CFGR {
         };
 
         // Calculate real APB1 clock
-        let pclk1 = hclk / u32::from(ppre1);
+        let pclk1 = hclk / ppre1 as u32;
 
-        assert!(unchecked || pclk1 <= PCLK1_MAX);
+        if !unchecked && pclk1 > PCLK1_MAX {
+            return Err(Error::Pclk1OutOfRange);
+        }
 
-        let pclk2 = self
-            .pclk2
-            .unwrap_or_else(|| crate::min_u32(PCLK2_MAX, hclk));
+        let pclk2 = match self.pclk2 {
+            Some(pclk2) => pclk2,
+            None => crate::min_u32(PCLK2_MAX, hclk),
+        };
         let (ppre2_bits, ppre2) = match (hclk + pclk2 - 1) / pclk2 {
             0 => unreachable!(),
             1 => (0b000, 1u8),
@@ -610,37 +938,379 @@ impl CFGR {
         };
 
         // Calculate real APB2 clock
-        let pclk2 = hclk / u32::from(ppre2);
+        let pclk2 = hclk / ppre2 as u32;
+
+        if !unchecked && pclk2 > PCLK2_MAX {
+            return Err(Error::Pclk2OutOfRange);
+        }
+
+        let flash_latency = (hclk - 1) / self.voltage_range.flash_latency_step();
+        if flash_latency > FLASH_LATENCY_MAX {
+            return Err(Error::SysclkOutOfRange);
+        }
 
-        assert!(unchecked || pclk2 <= PCLK2_MAX);
+        let (mco1, mco2) = self.mco_clocks(sysclk, plls.pllsysclk, plls.i2s.plli2sclk());
+
+        let pclk_mul = if ppre1 == 1 { 1 } else { 2 };
+        let timclk1 = Hertz::from_raw(pclk1 * pclk_mul);
+
+        let pclk_mul = if ppre2 == 1 { 1 } else { 2 };
+        let timclk2 = Hertz::from_raw(pclk2 * pclk_mul);
+
+        let clocks = Clocks {
+            hclk: Hertz::from_raw(hclk),
+            pclk1: Hertz::from_raw(pclk1),
+            pclk2: Hertz::from_raw(pclk2),
+            timclk1,
+            timclk2,
+            sysclk: Hertz::from_raw(sysclk),
+            pll48clk: hertz(plls.pll48clk),
+            voltage_range: self.voltage_range,
 
-        Self::flash_setup(sysclk);
+            #[cfg(not(feature = "rcc_i2s_apb"))]
+            i2s_clk: hertz(plls.i2s.i2s_clk),
+            #[cfg(feature = "rcc_i2s_apb")]
+            i2s_apb1_clk: hertz(plls.i2s.apb1.i2s_clk),
+            #[cfg(feature = "rcc_i2s_apb")]
+            i2s_apb2_clk: hertz(plls.i2s.apb2.i2s_clk),
 
-        if self.hse.is_some() {
+            #[cfg(feature = "sai")]
+            #[cfg(not(feature = "sai2"))]
+            saia_clk: hertz(plls.sai.sai1_clk),
+            #[cfg(feature = "sai")]
+            #[cfg(not(feature = "sai2"))]
+            saib_clk: hertz(plls.sai.sai2_clk),
+            #[cfg(feature = "sai2")]
+            sai1_clk: hertz(plls.sai.sai1_clk),
+            #[cfg(feature = "sai2")]
+            sai2_clk: hertz(plls.sai.sai2_clk),
+
+            #[cfg(feature = "ltdc")]
+            ltdc_clk: hertz(plls.ltdc_clk),
+
+            mco1: hertz(mco1),
+            mco2: hertz(mco2),
+
+            kernel: self.kernel,
+            #[cfg(feature = "spdifrx")]
+            spdifrx_clk: hertz(self.spdifrx_clk(&plls)),
+        };
+
+        Ok(ClockPlan {
+            cfgr: *self,
+            clocks,
+            plls,
+            sysclk_on_pll,
+            hpre_bits,
+            ppre1_bits,
+            ppre2_bits,
+            flash_latency: flash_latency as u8,
+            vos: vos(hclk),
+        })
+    }
+}
+
+/// Clock tree computed by [`CFGR::solve`]
+///
+/// Contains the register settings and resulting frequencies, the deviation of each
+/// frequency from the requested one is available from the `*_error` methods.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ClockPlan {
+    cfgr: CFGR,
+    clocks: Clocks,
+    plls: pll::PllSetup,
+    sysclk_on_pll: bool,
+    hpre_bits: u8,
+    ppre1_bits: u8,
+    ppre2_bits: u8,
+    flash_latency: u8,
+    vos: u8,
+}
+
+impl ClockPlan {
+    /// Returns the frequencies set up by this plan
+    pub fn clocks(&self) -> Clocks {
+        self.clocks
+    }
+
+    /// Returns the PLL configurations
+    pub fn plls(&self) -> &pll::PllSetup {
+        &self.plls
+    }
+
+    /// Returns the absolute error of SYSCLK in Hz, if it was requested
+    pub fn sysclk_error(&self) -> Option<u32> {
+        error(self.cfgr.sysclk, Some(self.clocks.sysclk))
+    }
+
+    /// Returns the absolute error of HCLK in Hz, if it was requested
+    pub fn hclk_error(&self) -> Option<u32> {
+        error(self.cfgr.hclk, Some(self.clocks.hclk))
+    }
+
+    /// Returns the absolute error of PCLK1 in Hz, if it was requested
+    pub fn pclk1_error(&self) -> Option<u32> {
+        error(self.cfgr.pclk1, Some(self.clocks.pclk1))
+    }
+
+    /// Returns the absolute error of PCLK2 in Hz, if it was requested
+    pub fn pclk2_error(&self) -> Option<u32> {
+        error(self.cfgr.pclk2, Some(self.clocks.pclk2))
+    }
+
+    /// Returns the absolute error of the 48 MHz clock in Hz, if it was requested
+    pub fn pll48clk_error(&self) -> Option<u32> {
+        error(
+            self.cfgr.pll48clk.then_some(48_000_000),
+            self.clocks.pll48clk,
+        )
+    }
+
+    /// Returns the absolute error of the I2S clock in Hz, if it was requested
+    #[cfg(not(feature = "rcc_i2s_apb"))]
+    pub fn i2s_clk_error(&self) -> Option<u32> {
+        error(self.cfgr.i2s_clk, self.clocks.i2s_clk)
+    }
+
+    /// Returns the absolute error of the APB1 I2S clock in Hz, if it was requested
+    #[cfg(feature = "rcc_i2s_apb")]
+    pub fn i2s_apb1_clk_error(&self) -> Option<u32> {
+        error(self.cfgr.i2s_apb1_clk, self.clocks.i2s_apb1_clk)
+    }
+
+    /// Returns the absolute error of the APB2 I2S clock in Hz, if it was requested
+    #[cfg(feature = "rcc_i2s_apb")]
+    pub fn i2s_apb2_clk_error(&self) -> Option<u32> {
+        error(self.cfgr.i2s_apb2_clk, self.clocks.i2s_apb2_clk)
+    }
+
+    /// Returns the absolute error of the SAIA clock in Hz, if it was requested
+    #[cfg(feature = "sai")]
+    #[cfg(not(feature = "sai2"))]
+    pub fn saia_clk_error(&self) -> Option<u32> {
+        error(self.cfgr.sai1_clk, self.clocks.saia_clk)
+    }
+
+    /// Returns the absolute error of the SAIB clock in Hz, if it was requested
+    #[cfg(feature = "sai")]
+    #[cfg(not(feature = "sai2"))]
+    pub fn saib_clk_error(&self) -> Option<u32> {
+        error(self.cfgr.sai2_clk, self.clocks.saib_clk)
+    }
+
+    /// Returns the absolute error of the SAI1 clock in Hz, if it was requested
+    #[cfg(feature = "sai2")]
+    pub fn sai1_clk_error(&self) -> Option<u32> {
+        error(self.cfgr.sai1_clk, self.clocks.sai1_clk)
+    }
+
+    /// Returns the absolute error of the SAI2 clock in Hz, if it was requested
+    #[cfg(feature = "sai2")]
+    pub fn sai2_clk_error(&self) -> Option<u32> {
+        error(self.cfgr.sai2_clk, self.clocks.sai2_clk)
+    }
+
+    /// Returns the absolute error of the LTDC clock in Hz, if it was requested
+    #[cfg(feature = "ltdc")]
+    pub fn ltdc_clk_error(&self) -> Option<u32> {
+        error(self.cfgr.ltdc_clk, self.clocks.ltdc_clk)
+    }
+
+    /// Runs the system from HSI and stops all PLLs, as well as HSE if it is not used with the
+    /// same bypass setting
+    fn enter_hsi(&self) {
+        let rcc = unsafe { &*RCC::ptr() };
+        let cfgr = &self.cfgr;
+
+        rcc.cr().modify(|_, w| w.hsion().set_bit());
+        while rcc.cr().read().hsirdy().bit_is_clear() {}
+        rcc.cfgr().modify(|_, w| w.sw().variant(SW::Hsi));
+        while rcc.cfgr().read().sws().bits() != SW::Hsi as u8 {}
+
+        // Over-drive can only be disabled while the system clock is HSI or HSE
+        #[cfg(any(feature = "gpio-f427", feature = "gpio-f446", feature = "gpio-f469"))]
+        if self.clocks.hclk.raw() <= 168_000_000 {
+            let pwr = unsafe { &*crate::pac::PWR::ptr() };
+            pwr.cr().modify(|_, w| {
+                w.odswen().clear_bit();
+                w.oden().clear_bit()
+            });
+        }
+
+        // PLL configuration can only be changed while all PLLs are off
+        rcc.cr().modify(|_, w| {
+            w.pllon().clear_bit();
+            #[cfg(not(feature = "gpio-f410"))]
+            w.plli2son().clear_bit();
+            #[cfg(feature = "sai")]
+            #[cfg(not(feature = "gpio-f413"))]
+            w.pllsaion().clear_bit();
+            w
+        });
+        while rcc.cr().read().pllrdy().bit_is_set() {}
+        #[cfg(not(feature = "gpio-f410"))]
+        while rcc.cr().read().plli2srdy().bit_is_set() {}
+        #[cfg(feature = "sai")]
+        #[cfg(not(feature = "gpio-f413"))]
+        while rcc.cr().read().pllsairdy().bit_is_set() {}
+
+        // HSE bypass can only be changed while HSE is off
+        let keep_hse = cfgr.hse.is_some() && rcc.cr().read().hsebyp().bit() == cfgr.hse_bypass;
+        rcc.cr().modify(|_, w| {
+            w.csson().clear_bit();
+            if !keep_hse {
+                w.hseon().clear_bit();
+            }
+            w
+        });
+        if !keep_hse {
+            while rcc.cr().read().hserdy().bit_is_set() {}
+            rcc.cr().modify(|_, w| w.hsebyp().clear_bit());
+        }
+    }
+
+    /// Writes the PLL configuration registers, PLLs must be disabled
+    fn pll_setup(&self) {
+        let rcc = unsafe { &*RCC::ptr() };
+        let plls = &self.plls;
+
+        // Even if we do not use the main PLL, we still need to set the PLL source as that setting
+        // applies to the I2S and SAI PLLs as well.
+        rcc.pllcfgr().write(|w| unsafe {
+            if let pll::MainPll::Used { m, n, p, q, .. } = plls.main {
+                w.pllm().bits(m as u8);
+                w.plln().bits(n as u16);
+                if let Some(p) = p {
+                    w.pllp().bits(p as u8 / 2 - 1);
+                }
+                if let Some(q) = q {
+                    w.pllq().bits(q as u8);
+                }
+            }
+            #[cfg(feature = "gpio-f410")]
+            if let pll::MainPll::Used { r: Some(r), .. } = plls.main {
+                w.pllr().bits(r as u8);
+            }
+            w.pllsrc().bit(plls.pllsrc_hse)
+        });
+
+        #[cfg(not(feature = "gpio-f410"))]
+        if let pll::I2sPll::Used { config, .. } = plls.i2s_pll {
+            // "M" may have been written before, but the value is identical.
+            #[cfg(feature = "rcc_shared_m")]
+            rcc.pllcfgr()
+                .modify(|_, w| unsafe { w.pllm().bits(config.m) });
+            rcc.plli2scfgr().modify(|_, w| unsafe {
+                #[cfg(not(feature = "rcc_shared_m"))]
+                w.plli2sm().bits(config.m);
+                w.plli2sn().bits(config.n);
+                w.plli2sr().bits(config.outdiv)
+            });
+        }
+
+        #[cfg(any(feature = "gpio-f412", feature = "gpio-f413"))]
+        if let Some(q) = plls.plli2sq {
+            rcc.plli2scfgr()
+                .modify(|_, w| unsafe { w.plli2sq().bits(q) });
+        }
+
+        #[cfg(feature = "gpio-f413")]
+        if let Some(div) = plls.plli2sdivr {
+            rcc.dckcfgr().modify(|_, w| w.plli2sdivr().set(div));
+        }
+
+        #[cfg(feature = "sai")]
+        #[cfg(not(feature = "gpio-f413"))]
+        if let pll::SaiPll::Used {
+            config,
+            saidiv,
+            #[cfg(feature = "ltdc")]
+            ltdc,
+            ..
+        } = plls.sai_pll
+        {
+            rcc.dckcfgr()
+                .modify(|_, w| w.pllsaidivq().set(saidiv as u8 - 1));
+            // "M" may have been written before, but the value is identical.
+            #[cfg(feature = "rcc_shared_m")]
+            rcc.pllcfgr()
+                .modify(|_, w| unsafe { w.pllm().bits(config.m) });
+            rcc.pllsaicfgr().modify(|_, w| unsafe {
+                #[cfg(not(feature = "rcc_shared_m"))]
+                w.pllsaim().bits(config.m);
+                w.pllsain().bits(config.n);
+                #[cfg(feature = "ltdc")]
+                if let Some(ltdc) = ltdc {
+                    w.pllsair().bits(ltdc.r);
+                }
+                w.pllsaiq().bits(config.outdiv)
+            });
+            #[cfg(feature = "ltdc")]
+            if let Some(ltdc) = ltdc {
+                // Divider is encoded as log2(div) - 1
+                rcc.dckcfgr()
+                    .modify(|_, w| w.pllsaidivr().set(ltdc.divr.trailing_zeros() as u8 - 1));
+            }
+        }
+
+        #[cfg(any(feature = "gpio-f446", feature = "gpio-f469"))]
+        if let Some(p) = plls.pllsaip {
+            rcc.pllsaicfgr()
+                .modify(|_, w| unsafe { w.pllsaip().bits(p / 2 - 1) });
+        }
+    }
+
+    fn apply(&self) -> Result<Clocks, Error> {
+        let rcc = unsafe { &*RCC::ptr() };
+        let cfgr = &self.cfgr;
+        let plls = &self.plls;
+
+        // MCO sources should be selected before oscillators and PLLs are enabled, RM0090 6.2.10
+        cfgr.mco_setup();
+
+        if cfgr.hse.is_some() {
             // enable HSE and wait for it to be ready
             rcc.cr().modify(|_, w| {
-                if self.hse_bypass {
+                if cfgr.hse_bypass {
                     w.hsebyp().bypassed();
                 }
                 w.hseon().set_bit()
             });
-            while rcc.cr().read().hserdy().bit_is_clear() {}
+            wait_ready(|| rcc.cr().read().hserdy().bit_is_set()).map_err(|_| {
+                if rcc.cfgr().read().sws().bits() == SW::Hsi as u8 {
+                    rcc.cr().modify(|_, w| w.hseon().clear_bit());
+                }
+                Error::HseTimeout
+            })?;
+
+            if cfgr.css {
+                // Clock security system can only be enabled while HSE is ready
+                rcc.cr().modify(|_, w| w.csson().set_bit());
+            }
         }
 
+        self.pll_setup();
+
+        // Enable clock for PWR peripheral
+        rcc.apb1enr().modify(|_, w| w.pwren().set_bit());
+
+        // Stall the pipeline to work around erratum 2.1.13 (DM00037591)
+        cortex_m::asm::dsb();
+
+        // Regulator voltage scaling can only be changed while the main PLL is off
+        let pwr = unsafe { &*crate::pac::PWR::ptr() };
+        #[cfg(feature = "gpio-f417")]
+        pwr.cr().modify(|_, w| w.vos().bit(self.vos == 1));
+        #[cfg(not(feature = "gpio-f417"))]
+        pwr.cr().modify(|_, w| unsafe { w.vos().bits(self.vos) });
+
         if plls.use_pll {
             // Enable PLL
             rcc.cr().modify(|_, w| w.pllon().set_bit());
 
             // Enable voltage regulator overdrive if HCLK is above the limit
             #[cfg(any(feature = "gpio-f427", feature = "gpio-f446", feature = "gpio-f469"))]
-            if hclk > 168_000_000 {
-                // Enable clock for PWR peripheral
-                rcc.apb1enr().modify(|_, w| w.pwren().set_bit());
-
-                // Stall the pipeline to work around erratum 2.1.13 (DM00037591)
-                cortex_m::asm::dsb();
-
-                let pwr = unsafe { &*crate::pac::PWR::ptr() };
+            if self.clocks.hclk.raw() > 168_000_000 {
                 pwr.cr().modify(|_, w| w.oden().set_bit());
                 while pwr.csr().read().odrdy().bit_is_clear() {}
                 pwr.cr().modify(|_, w| w.odswen().set_bit());
@@ -648,7 +1318,12 @@ impl CFGR {
             }
 
             // Wait for PLL to stabilise
-            while rcc.cr().read().pllrdy().bit_is_clear() {}
+            wait_ready(|| rcc.cr().read().pllrdy().bit_is_set()).map_err(|_| {
+                if rcc.cfgr().read().sws().bits() != SW::Pll as u8 {
+                    rcc.cr().modify(|_, w| w.pllon().clear_bit());
+                }
+                Error::PllTimeout
+            })?;
         }
 
         #[cfg(not(feature = "gpio-f410"))]
@@ -657,7 +1332,10 @@ impl CFGR {
             rcc.cr().modify(|_, w| w.plli2son().set_bit());
 
             // Wait for PLL to stabilise
-            while rcc.cr().read().plli2srdy().bit_is_clear() {}
+            wait_ready(|| rcc.cr().read().plli2srdy().bit_is_set()).map_err(|_| {
+                rcc.cr().modify(|_, w| w.plli2son().clear_bit());
+                Error::Plli2sTimeout
+            })?;
         }
 
         #[cfg(feature = "sai")]
@@ -667,19 +1345,25 @@ impl CFGR {
             rcc.cr().modify(|_, w| w.pllsaion().set_bit());
 
             // Wait for PLL to stabilise
-            while rcc.cr().read().pllsairdy().bit_is_clear() {}
+            wait_ready(|| rcc.cr().read().pllsairdy().bit_is_set()).map_err(|_| {
+                rcc.cr().modify(|_, w| w.pllsaion().clear_bit());
+                Error::PllsaiTimeout
+            })?;
         }
 
+        CFGR::flash_setup(self.flash_latency, cfgr.voltage_range);
+
         // Select I2S and SAI clocks
         plls.i2s.config_clocksel();
         #[cfg(feature = "sai")]
         plls.sai.config_clocksel();
+        cfgr.kernel.setup();
 
         // Set scaling factors
         rcc.cfgr().modify(|_, w| unsafe {
-            w.ppre2().bits(ppre2_bits);
-            w.ppre1().bits(ppre1_bits);
-            w.hpre().variant(hpre_bits)
+            w.ppre2().bits(self.ppre2_bits);
+            w.ppre1().bits(self.ppre1_bits);
+            w.hpre().bits(self.hpre_bits)
         });
 
         // Wait for the new prescalers to kick in
@@ -688,99 +1372,154 @@ impl CFGR {
 
         // Select system clock source
         rcc.cfgr().modify(|_, w| {
-            w.sw().variant(if sysclk_on_pll {
+            w.sw().variant(if self.sysclk_on_pll {
                 SW::Pll
-            } else if self.hse.is_some() {
+            } else if cfgr.hse.is_some() {
                 SW::Hse
             } else {
                 SW::Hsi
             })
         });
 
-        let pclk_mul = if ppre1 == 1 { 1 } else { 2 };
-        let timclk1 = Hertz::from_raw(pclk1 * pclk_mul);
+        Ok(cfgr.mco_check(self.clocks))
+    }
+}
 
-        let pclk_mul = if ppre2 == 1 { 1 } else { 2 };
-        let timclk2 = Hertz::from_raw(pclk2 * pclk_mul);
+/// Returns the lowest regulator voltage scale supporting `hclk`
+const fn vos(hclk: u32) -> u8 {
+    // Scale 3 is 0b01, scale 2 is 0b10 and scale 1 is 0b11
+    #[cfg(feature = "gpio-f401")]
+    let vos = if hclk <= 60_000_000 { 0b01 } else { 0b10 };
+
+    #[cfg(any(
+        feature = "gpio-f410",
+        feature = "gpio-f411",
+        feature = "gpio-f412",
+        feature = "gpio-f413",
+    ))]
+    let vos = if hclk <= 64_000_000 {
+        0b01
+    } else if hclk <= 84_000_000 {
+        0b10
+    } else {
+        0b11
+    };
 
-        let clocks = Clocks {
-            hclk: hclk.Hz(),
-            pclk1: pclk1.Hz(),
-            pclk2: pclk2.Hz(),
-            timclk1,
-            timclk2,
-            sysclk: sysclk.Hz(),
-            pll48clk: plls.pll48clk.map(Hertz::from_raw),
+    // Scale 2 is 0, scale 1 is 1
+    #[cfg(feature = "gpio-f417")]
+    let vos = if hclk <= 144_000_000 { 0 } else { 1 };
+
+    #[cfg(any(feature = "gpio-f427", feature = "gpio-f446", feature = "gpio-f469"))]
+    let vos = if hclk <= 120_000_000 {
+        0b01
+    } else if hclk <= 144_000_000 {
+        0b10
+    } else {
+        0b11
+    };
 
-            #[cfg(not(feature = "rcc_i2s_apb"))]
-            i2s_clk: plls.i2s.i2s_clk.map(Hertz::from_raw),
-            #[cfg(feature = "rcc_i2s_apb")]
-            i2s_apb1_clk: plls.i2s.apb1.i2s_clk.map(Hertz::from_raw),
-            #[cfg(feature = "rcc_i2s_apb")]
-            i2s_apb2_clk: plls.i2s.apb2.i2s_clk.map(Hertz::from_raw),
+    vos
+}
 
-            #[cfg(feature = "sai")]
-            #[cfg(not(feature = "sai2"))]
-            saia_clk: plls.sai.sai1_clk.map(Hertz::from_raw),
-            #[cfg(feature = "sai")]
-            #[cfg(not(feature = "sai2"))]
-            saib_clk: plls.sai.sai2_clk.map(Hertz::from_raw),
-            #[cfg(feature = "sai2")]
-            sai1_clk: plls.sai.sai1_clk.map(Hertz::from_raw),
-            #[cfg(feature = "sai2")]
-            sai2_clk: plls.sai.sai2_clk.map(Hertz::from_raw),
-        };
+fn error(requested: Option<u32>, real: Option<Hertz>) -> Option<u32> {
+    Some(requested?.abs_diff(real?.raw()))
+}
 
-        if self.pll48clk {
-            assert!(clocks.is_pll48clk_valid());
+/// Clock configuration error
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Requested system clock can not be generated from the PLL source
+    SysclkUnreachable,
+    /// System clock is out of `SYSCLK_MIN..=SYSCLK_MAX`, or HCLK needs more flash wait states
+    /// than available in the supply voltage range
+    SysclkOutOfRange,
+    /// APB1 clock is above `PCLK1_MAX`
+    Pclk1OutOfRange,
+    /// APB2 clock is above `PCLK2_MAX`
+    Pclk2OutOfRange,
+    /// PLL48 clock is out of USB tolerance
+    Pll48clkInvalid,
+    /// Requested I2S clocks (or SAI clocks on STM32F413/423) can not be generated by the I2S PLL
+    Plli2sUnreachable,
+    /// Requested SAI or 48 MHz clocks can not be generated by the SAI PLL
+    PllsaiUnreachable,
+    /// HSE oscillator did not start
+    HseTimeout,
+    /// Main PLL did not lock
+    PllTimeout,
+    /// I2S PLL did not lock
+    Plli2sTimeout,
+    /// SAI PLL did not lock
+    PllsaiTimeout,
+    /// SAI, LTDC and 48 MHz clocks can not be generated by PLLSAI at the same time
+    PllsaiConflict,
+}
+
+/// Polls of a ready flag before timeout, about 100 ms at 16 MHz
+const READY_TIMEOUT: u32 = 16_000;
+
+fn wait_ready(ready: impl Fn() -> bool) -> Result<(), ()> {
+    for _ in 0..READY_TIMEOUT {
+        if ready() {
+            return Ok(());
         }
+        cortex_m::asm::delay(100);
+    }
+    Err(())
+}
 
-        clocks
+const fn is_pll48clk_valid(pll48clk: Option<u32>) -> bool {
+    // USB specification allows +-0.25%
+    match pll48clk {
+        Some(freq) => 48_000_000_u32.abs_diff(freq) <= 120_000,
+        None => false,
     }
 }
 
+const fn hertz(freq: Option<u32>) -> Option<Hertz> {
+    match freq {
+        Some(freq) => Some(Hertz::from_raw(freq)),
+        None => None,
+    }
+}
+
+/// Returns `true` if both frequencies are set and equal
+const fn same_clk(a: Option<u32>, b: Option<u32>) -> bool {
+    matches!((a, b), (Some(a), Some(b)) if a == b)
+}
+
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 struct I2sClocks {
-    /// True if the clock for the APB1 I2S instances is identical to I2S_CKIN.
+    /// Source of the clock for the APB1 I2S instances, never `Auto`.
     #[cfg(feature = "rcc_i2s_apb")]
-    i2s_apb1_ext: bool,
-    /// True if the clock for the APB2 I2S instances is identical to I2S_CKIN.
+    i2s_apb1_src: I2sClkSrc,
+    /// Source of the clock for the APB2 I2S instances, never `Auto`.
     #[cfg(feature = "rcc_i2s_apb")]
-    i2s_apb2_ext: bool,
-    /// True if the clock for I2S is identical to I2S_CKIN.
+    i2s_apb2_src: I2sClkSrc,
+    /// Source of the I2S clock, never `Auto`.
     #[cfg(not(feature = "rcc_i2s_apb"))]
-    i2s_ext: bool,
+    i2s_src: I2sClkSrc,
     /// Target for the I2S PLL output.
     pll_i2s_clk: Option<u32>,
 }
 
 impl I2sClocks {
-    fn real(&self, pll_i2s_clk: Option<u32>, i2s_ckin: Option<u32>) -> RealI2sClocks {
+    const fn real(
+        &self,
+        pll_i2s_clk: Option<u32>,
+        i2s_ckin: Option<u32>,
+        pllsrcclk: u32,
+    ) -> RealI2sClocks {
         #[cfg(feature = "rcc_i2s_apb")]
         let clk = RealI2sClocks {
-            apb1: RealI2sClock {
-                i2s_ext: self.i2s_apb1_ext,
-                i2s_clk: if self.i2s_apb1_ext {
-                    i2s_ckin
-                } else {
-                    pll_i2s_clk
-                },
-            },
-            apb2: RealI2sClock {
-                i2s_ext: self.i2s_apb2_ext,
-                i2s_clk: if self.i2s_apb2_ext {
-                    i2s_ckin
-                } else {
-                    pll_i2s_clk
-                },
-            },
+            apb1: RealI2sClock::new(self.i2s_apb1_src, pll_i2s_clk, i2s_ckin, pllsrcclk),
+            apb2: RealI2sClock::new(self.i2s_apb2_src, pll_i2s_clk, i2s_ckin, pllsrcclk),
         };
         #[cfg(not(feature = "rcc_i2s_apb"))]
-        let clk = RealI2sClocks {
-            i2s_ext: self.i2s_ext,
-            i2s_clk: if self.i2s_ext { i2s_ckin } else { pll_i2s_clk },
-        };
+        let clk = RealI2sClock::new(self.i2s_src, pll_i2s_clk, i2s_ckin, pllsrcclk);
         clk
     }
 }
@@ -788,10 +1527,31 @@ impl I2sClocks {
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 struct RealI2sClock {
-    i2s_ext: bool,
+    src: I2sClkSrc,
     i2s_clk: Option<u32>,
 }
 
+impl RealI2sClock {
+    #[cfg_attr(
+        not(any(feature = "gpio-f410", feature = "rcc_i2s_apb")),
+        allow(unused_variables)
+    )]
+    const fn new(
+        src: I2sClkSrc,
+        pll_i2s_clk: Option<u32>,
+        i2s_ckin: Option<u32>,
+        pllsrcclk: u32,
+    ) -> Self {
+        let i2s_clk = match src {
+            I2sClkSrc::Auto | I2sClkSrc::Pll => pll_i2s_clk,
+            I2sClkSrc::Ckin => i2s_ckin,
+            #[cfg(any(feature = "gpio-f410", feature = "rcc_i2s_apb"))]
+            I2sClkSrc::PllSrc => Some(pllsrcclk),
+        };
+        Self { src, i2s_clk }
+    }
+}
+
 #[cfg(feature = "rcc_i2s_apb")]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
@@ -804,37 +1564,51 @@ struct RealI2sClocks {
 use RealI2sClock as RealI2sClocks;
 
 impl RealI2sClocks {
+    /// Output of the I2S PLL, if it is used
+    const fn plli2sclk(&self) -> Option<u32> {
+        #[cfg(feature = "rcc_i2s_apb")]
+        let clk = if matches!(self.apb1.src, I2sClkSrc::Pll) {
+            self.apb1.i2s_clk
+        } else if matches!(self.apb2.src, I2sClkSrc::Pll) {
+            self.apb2.i2s_clk
+        } else {
+            None
+        };
+        #[cfg(not(feature = "rcc_i2s_apb"))]
+        let clk = if matches!(self.src, I2sClkSrc::Pll) {
+            self.i2s_clk
+        } else {
+            None
+        };
+        clk
+    }
+
     fn config_clocksel(&self) {
         let rcc = unsafe { &*RCC::ptr() };
 
         #[cfg(not(feature = "gpio-f410"))]
         #[cfg(not(feature = "rcc_i2s_apb"))]
-        rcc.cfgr().modify(|_, w| {
-            if self.i2s_ext {
-                w.i2ssrc().ckin()
-            } else {
-                w.i2ssrc().plli2s()
-            }
+        rcc.cfgr().modify(|_, w| match self.src {
+            I2sClkSrc::Ckin => w.i2ssrc().ckin(),
+            _ => w.i2ssrc().plli2s(),
         });
         #[cfg(feature = "gpio-f410")]
-        rcc.dckcfgr().modify(|_, w| {
-            if self.i2s_ext {
-                w.i2ssrc().i2s_ckin()
-            } else {
-                w.i2ssrc().pllclkr()
-            }
+        rcc.dckcfgr().modify(|_, w| match self.src {
+            I2sClkSrc::Ckin => w.i2ssrc().i2s_ckin(),
+            I2sClkSrc::PllSrc => w.i2ssrc().hsi_hse(),
+            _ => w.i2ssrc().pllclkr(),
         });
         #[cfg(feature = "rcc_i2s_apb")]
         rcc.dckcfgr().modify(|_, w| {
-            if self.apb1.i2s_ext {
-                w.i2s1src().i2s_ckin()
-            } else {
-                w.i2s1src().plli2sr()
+            match self.apb1.src {
+                I2sClkSrc::Ckin => w.i2s1src().i2s_ckin(),
+                I2sClkSrc::PllSrc => w.i2s1src().hsi_hse(),
+                _ => w.i2s1src().plli2sr(),
             };
-            if self.apb2.i2s_ext {
-                w.i2s2src().i2s_ckin()
-            } else {
-                w.i2s2src().plli2sr()
+            match self.apb2.src {
+                I2sClkSrc::Ckin => w.i2s2src().i2s_ckin(),
+                I2sClkSrc::PllSrc => w.i2s2src().hsi_hse(),
+                _ => w.i2s2src().plli2sr(),
             }
         });
     }
@@ -855,7 +1629,7 @@ struct SaiClocks {
 
 #[cfg(feature = "sai")]
 impl SaiClocks {
-    fn real(&self, pll_sai_clk: Option<u32>, i2s_ckin: Option<u32>) -> RealSaiClocks {
+    const fn real(&self, pll_sai_clk: Option<u32>, i2s_ckin: Option<u32>) -> RealSaiClocks {
         RealSaiClocks {
             sai1_ext: self.sai1_ext,
             #[cfg(not(feature = "sai2"))]
@@ -913,7 +1687,8 @@ impl RealSaiClocks {
 
 /// Frozen clock frequencies
 ///
-/// The existence of this value indicates that the clock configuration can no longer be changed
+/// The existence of this value indicates that the clock configuration can no longer be changed,
+/// except by [`CFGR::refreeze`] returning a new one
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub struct Clocks {
@@ -924,6 +1699,7 @@ pub struct Clocks {
     timclk2: Hertz,
     sysclk: Hertz,
     pll48clk: Option<Hertz>,
+    voltage_range: VoltageRange,
 
     #[cfg(not(feature = "rcc_i2s_apb"))]
     i2s_clk: Option<Hertz>,
@@ -942,6 +1718,16 @@ pub struct Clocks {
     sai1_clk: Option<Hertz>,
     #[cfg(feature = "sai2")]
     sai2_clk: Option<Hertz>,
+
+    #[cfg(feature = "ltdc")]
+    ltdc_clk: Option<Hertz>,
+
+    mco1: Option<Hertz>,
+    mco2: Option<Hertz>,
+
+    kernel: kernel::KernelClocks,
+    #[cfg(feature = "spdifrx")]
+    spdifrx_clk: Option<Hertz>,
 }
 
 impl Clocks {
@@ -975,7 +1761,14 @@ impl Clocks {
         self.sysclk
     }
 
-    /// Returns the frequency of the PLL48 clock line
+    /// Returns the declared supply voltage range
+    pub fn voltage_range(&self) -> VoltageRange {
+        self.voltage_range
+    }
+
+    /// Returns the frequency of the 48 MHz clock line
+    ///
+    /// It is generated by the PLL selected with `CFGR::clk48_src` on F412, F413, F446 and F469.
     pub fn pll48clk(&self) -> Option<Hertz> {
         self.pll48clk
     }
@@ -983,10 +1776,7 @@ impl Clocks {
     /// Returns true if the PLL48 clock is within USB
     /// specifications. It is required to use the USB functionality.
     pub fn is_pll48clk_valid(&self) -> bool {
-        // USB specification allows +-0.25%
-        self.pll48clk
-            .map(|freq| 48_000_000_u32.abs_diff(freq.raw()) <= 120_000)
-            .unwrap_or_default()
+        is_pll48clk_valid(self.pll48clk.map(|f| f.raw()))
     }
 
     /// Returns the frequency of the I2S clock.
@@ -1027,4 +1817,10 @@ impl Clocks {
     pub fn sai2_clk(&self) -> Option<Hertz> {
         self.sai2_clk
     }
+
+    /// Returns the frequency of the LTDC pixel clock.
+    #[cfg(feature = "ltdc")]
+    pub fn ltdc_clk(&self) -> Option<Hertz> {
+        self.ltdc_clk
+    }
 }
@@ -1,5 +1,16 @@
-use super::CFGR;
-use crate::pac::RCC;
+//! PLL configuration search
+//!
+//! The search does not access registers, so it also runs on the host.
+//! [`CFGR::solve`] uses it to compute the complete clock tree.
+
+#[cfg(any(
+    feature = "gpio-f412",
+    feature = "gpio-f413",
+    feature = "gpio-f446",
+    feature = "gpio-f469"
+))]
+use super::Clk48Src;
+use super::{Error, CFGR};
 
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
@@ -7,206 +18,359 @@ pub struct PllSetup {
     pub use_pll: bool,
     pub pllsysclk: Option<u32>,
     pub pll48clk: Option<u32>,
+    pub(super) main: MainPll,
+    pub(super) pllsrc_hse: bool,
 
     #[cfg(not(feature = "gpio-f410"))]
     pub use_i2spll: bool,
+    #[cfg(not(feature = "gpio-f410"))]
+    pub(super) i2s_pll: I2sPll,
+    #[cfg(feature = "gpio-f413")]
+    pub(super) plli2sdivr: Option<u8>,
+    /// "Q" divider of the I2S PLL generating the 48 MHz clock
+    #[cfg(any(feature = "gpio-f412", feature = "gpio-f413"))]
+    pub(super) plli2sq: Option<u8>,
     pub(super) i2s: super::RealI2sClocks,
 
     #[cfg(feature = "sai")]
     #[cfg(not(feature = "gpio-f413"))]
     pub use_saipll: bool,
     #[cfg(feature = "sai")]
+    #[cfg(not(feature = "gpio-f413"))]
+    pub(super) sai_pll: SaiPll,
+    /// "P" divider of the SAI PLL generating the 48 MHz clock
+    #[cfg(any(feature = "gpio-f446", feature = "gpio-f469"))]
+    pub(super) pllsaip: Option<u8>,
+    #[cfg(feature = "sai")]
     pub(super) sai: super::RealSaiClocks,
+    #[cfg(feature = "ltdc")]
+    pub ltdc_clk: Option<u32>,
 }
 
 impl PllSetup {
     #[cfg(feature = "gpio-f410")]
     #[inline(always)]
-    pub fn from_cfgr(cfgr: &CFGR, pllsrcclk: u32, pllsysclk: Option<u32>) -> Self {
-        let i2s_clocks = cfgr.i2s_clocks();
+    pub const fn from_cfgr(
+        cfgr: &CFGR,
+        pllsrcclk: u32,
+        pllsysclk: Option<u32>,
+    ) -> Result<Self, Error> {
+        let i2s_clocks = const_try!(cfgr.i2s_clocks());
 
         let (main_pll, plli2sclk) = if let Some(i2s_clk) = i2s_clocks.pll_i2s_clk {
             // The I2S frequency is generated by the main PLL. The frequency needs to be accurate,
             // so we need an expensive full PLL configuration search.
-            MainPll::setup_with_i2s(
+            const_try!(MainPll::setup_with_i2s(
                 pllsrcclk,
-                cfgr.hse.is_some(),
                 pllsysclk,
                 cfgr.pll48clk,
-                i2s_clk,
-            )
+                i2s_clk
+            ))
         } else {
             (
-                MainPll::fast_setup(pllsrcclk, cfgr.hse.is_some(), pllsysclk, cfgr.pll48clk),
+                const_try!(MainPll::fast_setup(pllsrcclk, pllsysclk, cfgr.pll48clk)),
                 None,
             )
         };
 
-        Self {
+        Ok(Self {
             use_pll: main_pll.use_pll(),
             pllsysclk: main_pll.pllsysclk(),
             pll48clk: main_pll.pll48clk(),
+            main: main_pll,
+            pllsrc_hse: cfgr.hse.is_some(),
 
-            i2s: i2s_clocks.real(plli2sclk, cfgr.i2s_ckin),
-        }
+            i2s: i2s_clocks.real(plli2sclk, cfgr.i2s_ckin, pllsrcclk),
+        })
     }
 
     #[cfg(feature = "gpio-f413")]
     #[inline(always)]
-    pub fn from_cfgr(cfgr: &CFGR, pllsrcclk: u32, pllsysclk: Option<u32>) -> Self {
-        let rcc = unsafe { &*RCC::ptr() };
-
-        let i2s_clocks = cfgr.i2s_clocks();
-        let sai_clocks = cfgr.sai_clocks();
+    pub const fn from_cfgr(
+        cfgr: &CFGR,
+        pllsrcclk: u32,
+        pllsysclk: Option<u32>,
+    ) -> Result<Self, Error> {
+        let i2s_clocks = const_try!(cfgr.i2s_clocks());
+        let sai_clocks = const_try!(cfgr.sai_clocks());
 
-        let main_pll = MainPll::fast_setup(pllsrcclk, cfgr.hse.is_some(), pllsysclk, cfgr.pll48clk);
+        let main_pll = const_try!(MainPll::fast_setup(
+            pllsrcclk,
+            pllsysclk,
+            cfgr.pll48clk && cfgr.kernel.clk48_on_main_pll(),
+        ));
 
-        let (i2s_pll, real_sai_clk) = if let Some(i2s_clk) = i2s_clocks.pll_i2s_clk {
+        let (i2s_pll, real_sai_clk, plli2sdivr) = if let Some(i2s_clk) = i2s_clocks.pll_i2s_clk {
             // Currently, we only support generating SAI/PLL clocks with the I2S PLL. This is only
             // really usable when the frequencies are identical or the I2S frequency is a multiple of
             // the SAI frequency. Therefore, we just optimize the PLL for the I2S frequency and then
             // derive the SAI frequency from the I2S frequency.
-            let i2s_pll = I2sPll::setup(pllsrcclk, Some(i2s_clk));
+            let i2s_pll = const_try!(I2sPll::setup(pllsrcclk, Some(i2s_clk)));
 
             if let Some(sai_clk) = sai_clocks.pll_sai_clk {
+                let plli2sclk = match i2s_pll.plli2sclk() {
+                    Some(plli2sclk) => plli2sclk,
+                    None => return Err(Error::Plli2sUnreachable),
+                };
                 let div = crate::min_u32(
-                    if let I2sPll::Used { plli2sclk, .. } = i2s_pll {
-                        crate::max_u32((plli2sclk + (sai_clk >> 1)) / sai_clk, 1)
-                    } else {
-                        panic!()
-                    },
+                    crate::max_u32((plli2sclk + (sai_clk >> 1)) / sai_clk, 1),
                     31,
                 );
-                rcc.dckcfgr().modify(|_, w| w.plli2sdivr().set(div as u8));
                 let real_sai_clk = sai_clk / div;
-                (i2s_pll, Some(real_sai_clk))
+                (i2s_pll, Some(real_sai_clk), Some(div as u8))
             } else {
-                (i2s_pll, None)
+                (i2s_pll, None, None)
             }
         } else if let Some(pll_sai_clk) = sai_clocks.pll_sai_clk {
             // We try all divider values to get the best approximation of the requested frequency.
             // NOTE: STM32F413/423 have a different divider range than other models!
-            let (i2s_pll, real_sai_clk, div) = (1..31)
-                .map(|div| {
-                    let i2s_pll = I2sPll::setup(pllsrcclk, Some(pll_sai_clk * div));
-                    let real_clk = if let I2sPll::Used { plli2sclk, .. } = i2s_pll {
-                        plli2sclk / div
-                    } else {
-                        panic!()
-                    };
-                    (i2s_pll, real_clk, div)
-                })
-                .min_by_key(|(_, real_clk, _)| (*real_clk as i32 - pll_sai_clk as i32).abs())
-                .unwrap();
-            rcc.dckcfgr().modify(|_, w| w.plli2sdivr().set(div as u8));
-            (i2s_pll, Some(real_sai_clk))
+            let mut best: Option<(I2sPll, u32, u32, i32)> = None;
+            let mut div = 1;
+            while div < 31 {
+                if let Ok(i2s_pll) = I2sPll::setup(pllsrcclk, Some(pll_sai_clk * div)) {
+                    if let Some(plli2sclk) = i2s_pll.plli2sclk() {
+                        let real_clk = plli2sclk / div;
+                        let error = (real_clk as i32 - pll_sai_clk as i32).abs();
+                        best = match best {
+                            Some((_, _, _, best_error)) if best_error <= error => best,
+                            _ => Some((i2s_pll, real_clk, div, error)),
+                        };
+                    }
+                }
+                div += 1;
+            }
+            let (i2s_pll, real_sai_clk, div) = match best {
+                Some((i2s_pll, real_sai_clk, div, _)) => (i2s_pll, real_sai_clk, div),
+                None => return Err(Error::Plli2sUnreachable),
+            };
+            (i2s_pll, Some(real_sai_clk), Some(div as u8))
         } else {
-            (I2sPll::Unused, None)
+            (I2sPll::Unused, None, None)
         };
 
-        Self {
+        let plli2sclk = i2s_pll.plli2sclk();
+        // The 48 MHz clock can be generated by the "Q" output of the I2S PLL.
+        let (i2s_pll, plli2sq, pll48clk) = match cfgr.kernel.clk48 {
+            Clk48Src::Pll => (i2s_pll, None, main_pll.pll48clk()),
+            Clk48Src::Plli2s => {
+                let (i2s_pll, q, clk48) = const_try!(i2s_pll.setup_clk48(pllsrcclk));
+                (i2s_pll, Some(q), Some(clk48))
+            }
+        };
+
+        Ok(Self {
             use_pll: main_pll.use_pll(),
             pllsysclk: main_pll.pllsysclk(),
-            pll48clk: main_pll.pll48clk(),
+            pll48clk,
+            main: main_pll,
+            pllsrc_hse: cfgr.hse.is_some(),
 
             use_i2spll: i2s_pll.use_pll(),
-            i2s: i2s_clocks.real(i2s_pll.plli2sclk(), cfgr.i2s_ckin),
+            i2s_pll,
+            plli2sdivr,
+            plli2sq,
+            i2s: i2s_clocks.real(plli2sclk, cfgr.i2s_ckin, pllsrcclk),
 
             sai: sai_clocks.real(real_sai_clk, cfgr.i2s_ckin),
-        }
+        })
     }
 
     #[cfg(not(any(feature = "gpio-f410", feature = "gpio-f413")))]
     #[inline(always)]
-    pub fn from_cfgr(cfgr: &CFGR, pllsrcclk: u32, pllsysclk: Option<u32>) -> Self {
-        let i2s_clocks = cfgr.i2s_clocks();
+    pub const fn from_cfgr(
+        cfgr: &CFGR,
+        pllsrcclk: u32,
+        pllsysclk: Option<u32>,
+    ) -> Result<Self, Error> {
+        let i2s_clocks = const_try!(cfgr.i2s_clocks());
         #[cfg(feature = "sai")]
-        let sai_clocks = cfgr.sai_clocks();
+        let sai_clocks = const_try!(cfgr.sai_clocks());
 
         // All PLLs are completely independent.
-        let main_pll = MainPll::fast_setup(pllsrcclk, cfgr.hse.is_some(), pllsysclk, cfgr.pll48clk);
+        let main_pll = const_try!(MainPll::fast_setup(
+            pllsrcclk,
+            pllsysclk,
+            cfgr.pll48clk && cfgr.kernel.clk48_on_main_pll(),
+        ));
+        let pll48clk = main_pll.pll48clk();
 
         #[cfg(not(feature = "rcc_shared_m"))]
-        let i2s_pll = I2sPll::setup(pllsrcclk, i2s_clocks.pll_i2s_clk);
+        let i2s_pll = const_try!(I2sPll::setup(pllsrcclk, i2s_clocks.pll_i2s_clk));
         #[cfg(feature = "rcc_shared_m")]
         // We have separate PLLs, but they share the "M" divider.
-        let i2s_pll = I2sPll::setup_shared_m(pllsrcclk, main_pll.m(), i2s_clocks.pll_i2s_clk);
+        let i2s_pll = const_try!(I2sPll::setup_shared_m(
+            pllsrcclk,
+            main_pll.m(),
+            i2s_clocks.pll_i2s_clk
+        ));
+        let plli2sclk = i2s_pll.plli2sclk();
+
+        // The 48 MHz clock can be generated by the "Q" output of the I2S PLL.
+        #[cfg(feature = "gpio-f412")]
+        let (i2s_pll, plli2sq, pll48clk) = match cfgr.kernel.clk48 {
+            Clk48Src::Pll => (i2s_pll, None, pll48clk),
+            Clk48Src::Plli2s => {
+                let (i2s_pll, q, clk48) = const_try!(i2s_pll.setup_clk48(pllsrcclk));
+                (i2s_pll, Some(q), Some(clk48))
+            }
+        };
 
         #[cfg(feature = "sai")]
         #[cfg(not(feature = "rcc_shared_m"))]
-        let sai_pll = SaiPll::setup(pllsrcclk, sai_clocks.pll_sai_clk);
+        let sai_pll = const_try!(SaiPll::setup(pllsrcclk, sai_clocks.pll_sai_clk));
         #[cfg(feature = "sai")]
         #[cfg(feature = "rcc_shared_m")]
-        let sai_pll = SaiPll::setup_shared_m(
+        #[cfg(not(feature = "ltdc"))]
+        let sai_pll = const_try!(SaiPll::setup_shared_m(
             pllsrcclk,
-            main_pll.m().or(i2s_pll.m()),
+            shared_m(main_pll, i2s_pll),
             sai_clocks.pll_sai_clk,
-        );
+        ));
+        // SAI, LTDC and on STM32F469/479 the 48 MHz clocks are all generated by PLLSAI.
+        #[cfg(feature = "ltdc")]
+        let sai_pll = match cfgr.ltdc_clk {
+            Some(ltdc_clk) => match SaiPll::setup_with_ltdc(
+                pllsrcclk,
+                shared_m(main_pll, i2s_pll),
+                sai_clocks.pll_sai_clk,
+                ltdc_clk,
+                !cfgr.kernel.clk48_on_main_pll(),
+            ) {
+                Some(sai_pll) => sai_pll,
+                None => return Err(Error::PllsaiConflict),
+            },
+            None => const_try!(SaiPll::setup_shared_m(
+                pllsrcclk,
+                shared_m(main_pll, i2s_pll),
+                sai_clocks.pll_sai_clk,
+            )),
+        };
+
+        // The 48 MHz clock can be generated by the "P" output of the SAI PLL.
+        #[cfg(any(feature = "gpio-f446", feature = "gpio-f469"))]
+        let (sai_pll, pllsaip, pll48clk) = match cfgr.kernel.clk48 {
+            Clk48Src::Pll => (sai_pll, None, pll48clk),
+            Clk48Src::Pllsai => {
+                #[cfg(feature = "rcc_shared_m")]
+                let m = shared_m(main_pll, i2s_pll);
+                #[cfg(not(feature = "rcc_shared_m"))]
+                let m = None;
+                let (sai_pll, p, clk48) = const_try!(sai_pll.setup_clk48(pllsrcclk, m));
+                (sai_pll, Some(p), Some(clk48))
+            }
+        };
 
-        Self {
+        Ok(Self {
             use_pll: main_pll.use_pll(),
             pllsysclk: main_pll.pllsysclk(),
-            pll48clk: main_pll.pll48clk(),
+            pll48clk,
+            main: main_pll,
+            pllsrc_hse: cfgr.hse.is_some(),
 
             use_i2spll: i2s_pll.use_pll(),
-            i2s: i2s_clocks.real(i2s_pll.plli2sclk(), cfgr.i2s_ckin),
+            i2s_pll,
+            #[cfg(feature = "gpio-f412")]
+            plli2sq,
+            i2s: i2s_clocks.real(plli2sclk, cfgr.i2s_ckin, pllsrcclk),
 
             #[cfg(feature = "sai")]
             use_saipll: sai_pll.use_pll(),
             #[cfg(feature = "sai")]
+            sai_pll,
+            #[cfg(any(feature = "gpio-f446", feature = "gpio-f469"))]
+            pllsaip,
+            #[cfg(feature = "sai")]
             sai: sai_clocks.real(sai_pll.sai_clk(), cfgr.i2s_ckin),
-        }
+            #[cfg(feature = "ltdc")]
+            ltdc_clk: sai_pll.ltdc_clk(),
+        })
+    }
+
+    /// Main PLL configuration
+    pub const fn main_pll(&self) -> MainPll {
+        self.main
+    }
+
+    /// I2S PLL configuration
+    #[cfg(not(feature = "gpio-f410"))]
+    pub const fn i2s_pll(&self) -> I2sPll {
+        self.i2s_pll
+    }
+
+    /// SAI PLL configuration
+    #[cfg(feature = "sai")]
+    #[cfg(not(feature = "gpio-f413"))]
+    pub const fn sai_pll(&self) -> SaiPll {
+        self.sai_pll
+    }
+}
+
+/// "M" divisor shared with the SAI PLL, taken from the main PLL or the I2S PLL
+#[cfg(feature = "sai")]
+#[cfg(feature = "rcc_shared_m")]
+const fn shared_m(main_pll: MainPll, i2s_pll: I2sPll) -> Option<u32> {
+    match main_pll.m() {
+        Some(m) => Some(m),
+        None => i2s_pll.m(),
     }
 }
 
-#[derive(Clone, Copy)]
+/// Main PLL configuration
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum MainPll {
     Used {
         pllsysclk: Option<u32>,
         pll48clk: Option<u32>,
         /// "M" divisor, required for the other PLLs on some MCUs.
-        #[allow(unused)]
         m: u32,
+        /// "N" multiplier
+        n: u32,
+        /// "P" divisor of the system clock output
+        p: Option<u32>,
+        /// "Q" divisor of the 48 MHz output
+        q: Option<u32>,
+        /// "R" divisor of the I2S output
+        r: Option<u32>,
     },
     Unused,
 }
 
 impl MainPll {
-    fn use_pll(&self) -> bool {
+    pub const fn use_pll(&self) -> bool {
         matches!(self, Self::Used { .. })
     }
-    fn pllsysclk(&self) -> Option<u32> {
+    pub const fn pllsysclk(&self) -> Option<u32> {
         match self {
             Self::Used { pllsysclk, .. } => *pllsysclk,
             Self::Unused => None,
         }
     }
-    fn pll48clk(&self) -> Option<u32> {
+    pub const fn pll48clk(&self) -> Option<u32> {
         match self {
             Self::Used { pll48clk, .. } => *pll48clk,
             Self::Unused => None,
         }
     }
     #[allow(unused)]
-    fn m(&self) -> Option<u32> {
-        if let Self::Used { m, .. } = self {
-            Some(*m)
-        } else {
-            None
+    pub const fn m(&self) -> Option<u32> {
+        match self {
+            Self::Used { m, .. } => Some(*m),
+            Self::Unused => None,
         }
     }
 
-    fn fast_setup(pllsrcclk: u32, use_hse: bool, pllsysclk: Option<u32>, pll48clk: bool) -> Self {
-        let sysclk = pllsysclk.unwrap_or(pllsrcclk);
-        if pllsysclk.is_none() && !pll48clk {
-            // Even if we do not use the main PLL, we still need to set the PLL source as that setting
-            // applies to the I2S and SAI PLLs as well.
-            unsafe { &*RCC::ptr() }
-                .pllcfgr()
-                .write(|w| w.pllsrc().bit(use_hse));
-
-            return Self::Unused;
-        }
+    /// Finds the main PLL configuration for `pllsysclk` and optionally 48 MHz clock
+    pub const fn fast_setup(
+        pllsrcclk: u32,
+        pllsysclk: Option<u32>,
+        pll48clk: bool,
+    ) -> Result<Self, Error> {
+        let sysclk = match pllsysclk {
+            Some(sysclk) => sysclk,
+            None if pll48clk => pllsrcclk,
+            None => return Ok(Self::Unused),
+        };
         // Input divisor from PLL source clock, must result to frequency in
         // the range from 1 to 2 MHz
         let pllm_min = (pllsrcclk + 1_999_999) / 2_000_000;
@@ -223,146 +387,196 @@ impl MainPll {
 
         // Find the lowest pllm value that minimize the difference between
         // target frequency and the real vco_out frequency.
-        let pllm = (pllm_min..=pllm_max)
-            .min_by_key(|pllm| {
-                let vco_in = pllsrcclk / pllm;
-                let plln = target_freq / vco_in;
-                target_freq - vco_in * plln
-            })
-            .unwrap();
+        let mut pllm = pllm_min;
+        let mut best_diff = u32::MAX;
+        let mut m = pllm_min;
+        while m <= pllm_max {
+            let vco_in = pllsrcclk / m;
+            let plln = target_freq / vco_in;
+            let diff = target_freq - vco_in * plln;
+            if diff < best_diff {
+                pllm = m;
+                best_diff = diff;
+            }
+            m += 1;
+        }
 
         let vco_in = pllsrcclk / pllm;
-        assert!((1_000_000..=2_000_000).contains(&vco_in));
+        if vco_in < 1_000_000 || vco_in > 2_000_000 {
+            return Err(if pllsysclk.is_some() {
+                Error::SysclkUnreachable
+            } else {
+                Error::Pll48clkInvalid
+            });
+        }
 
         // Main scaler, must result in >= 100MHz (>= 192MHz for F401)
         // and <= 432MHz, min 50, max 432
         let plln = if pll48clk {
             // try the different valid pllq according to the valid
             // main scaller values, and take the best
-            let pllq = (4..=9)
-                .min_by_key(|pllq| {
-                    let plln = 48_000_000 * pllq / vco_in;
-                    let pll48_diff = 48_000_000 - vco_in * plln / pllq;
-                    let sysclk_diff = (sysclk as i32 - (vco_in * plln / sysclk_div) as i32).abs();
-                    (pll48_diff, sysclk_diff)
-                })
-                .unwrap();
-            48_000_000 * pllq / vco_in
+            let mut best_pllq = 4;
+            let mut best_diff = (u32::MAX, i32::MAX);
+            let mut pllq = 4;
+            while pllq <= 9 {
+                let plln = 48_000_000 * pllq / vco_in;
+                let pll48_diff = 48_000_000 - vco_in * plln / pllq;
+                let sysclk_diff = (sysclk as i32 - (vco_in * plln / sysclk_div) as i32).abs();
+                if pll48_diff < best_diff.0
+                    || pll48_diff == best_diff.0 && sysclk_diff < best_diff.1
+                {
+                    best_pllq = pllq;
+                    best_diff = (pll48_diff, sysclk_diff);
+                }
+                pllq += 1;
+            }
+            48_000_000 * best_pllq / vco_in
         } else {
             sysclk * sysclk_div / vco_in
         };
-        let pllp = (sysclk_div / 2) - 1;
 
         let pllq = (vco_in * plln + 47_999_999) / 48_000_000;
         let real_pll48clk = vco_in * plln / pllq;
 
-        unsafe { &*RCC::ptr() }.pllcfgr().write(|w| unsafe {
-            w.pllm().bits(pllm as u8);
-            w.plln().bits(plln as u16);
-            w.pllp().bits(pllp as u8);
-            w.pllq().bits(pllq as u8);
-            w.pllsrc().bit(use_hse)
-        });
-
         let real_pllsysclk = vco_in * plln / sysclk_div;
 
-        Self::Used {
+        Ok(Self::Used {
             pllsysclk: Some(real_pllsysclk),
-            pll48clk: pll48clk.then_some(real_pll48clk),
+            pll48clk: if pll48clk { Some(real_pll48clk) } else { None },
             m: pllm,
-        }
+            n: plln,
+            p: Some(sysclk_div),
+            q: Some(pllq),
+            r: None,
+        })
     }
 }
 
 #[cfg(feature = "gpio-f410")]
 impl MainPll {
-    fn setup_with_i2s(
+    const fn setup_with_i2s(
         pllsrcclk: u32,
-        use_hse: bool,
         pllsysclk: Option<u32>,
         pll48clk: bool,
         plli2sclk: u32,
-    ) -> (Self, Option<u32>) {
-        use super::{SYSCLK_MAX, SYSCLK_MIN};
-
+    ) -> Result<(Self, Option<u32>), Error> {
         // Input divisor from PLL source clock, must result to frequency in
         // the range from 1 to 2 MHz
         let pllm_min = (pllsrcclk + 1_999_999) / 2_000_000;
         let pllm_max = pllsrcclk / 1_000_000;
 
-        let (pllm, plln, pllp, pllq, pllr, _) = (pllm_min..=pllm_max)
-            .filter_map(|m| {
-                let vco_in = pllsrcclk / m;
-
-                // The VCO output must be within 100 and 432 MHz.
-                let plln_min = (100_000_000 + vco_in - 1) / vco_in;
-                let plln_max = 432_000_000 / vco_in;
-
-                (plln_min..=plln_max)
-                    .filter_map(|n| {
-                        let vco_out = vco_in * n;
-
-                        // The "P" divider value must be even (2, 4, 6, 8).
-                        let p = if let Some(pllsysclk) = pllsysclk {
-                            let (p, p_output, p_error) = Self::best_divider(
-                                vco_out,
-                                SYSCLK_MIN * 2,
-                                pllsysclk * 2,
-                                SYSCLK_MAX * 2,
-                                1,
-                                4,
-                            )?;
-                            Some((p * 2, p_output / 2, p_error / 2))
-                        } else {
-                            None
-                        };
-
-                        // The 48 MHz clock must be accurate within 0.25% for USB.
-                        let q = pll48clk.then_some(Self::best_divider(
-                            vco_out, 47_880_000, 48_000_000, 48_120_000, 2, 15,
-                        )?);
-
-                        // We do not set any accuracy requirements for I2S, as on F410 this frequency is
-                        // provided on a best-effort basis.
-                        // TODO: What is the maximum valid input frequency for I2S?
-                        let r = Self::best_divider(vco_out, 0, plli2sclk, u32::MAX, 2, 15)?;
-
-                        let error = p.map(|(_, _, error)| error).unwrap_or(0)
-                            + p.map(|(_, _, error)| error).unwrap_or(0)
-                            + r.2;
-
-                        Some((m, n, p.map(|p| p.0), q.map(|q| q.0), r.0, error))
-                    })
-                    .min_by_key(|(_, _, _, _, _, error)| *error)
-            })
-            .min_by_key(|(_, _, _, _, _, error)| *error)
-            .expect("could not find a valid main PLL configuration");
-
-        unsafe { &*RCC::ptr() }.pllcfgr().write(|w| unsafe {
-            w.pllm().bits(pllm as u8);
-            w.plln().bits(plln as u16);
-            if let Some(pllp) = pllp {
-                w.pllp().bits(pllp as u8 / 2 - 1);
-            }
-            if let Some(pllq) = pllq {
-                w.pllq().bits(pllq as u8);
+        // M, N, P, Q, R and the error of the best dividers found
+        #[allow(clippy::type_complexity)]
+        let mut best: Option<(u32, u32, Option<u32>, Option<u32>, u32, u32)> = None;
+        let mut m = pllm_min;
+        while m <= pllm_max {
+            let vco_in = pllsrcclk / m;
+
+            // The VCO output must be within 100 and 432 MHz.
+            let plln_min = (100_000_000 + vco_in - 1) / vco_in;
+            let plln_max = 432_000_000 / vco_in;
+
+            let mut n = plln_min;
+            while n <= plln_max {
+                if let Some((p, q, r, error)) =
+                    Self::optimize_dividers(vco_in * n, pllsysclk, pll48clk, plli2sclk)
+                {
+                    best = match best {
+                        Some((.., best_error)) if best_error <= error => best,
+                        _ => Some((m, n, p, q, r, error)),
+                    };
+                }
+                n += 1;
             }
-            w.pllr().bits(pllr as u8);
-            w.pllsrc().bit(use_hse)
-        });
+            m += 1;
+        }
+        let (pllm, plln, pllp, pllq, pllr) = match best {
+            Some((m, n, p, q, r, _)) => (m, n, p, q, r),
+            None => return Err(Error::SysclkUnreachable),
+        };
 
-        let real_pllsysclk = pllp.map(|pllp| pllsrcclk / pllm * plln / pllp);
-        let real_pll48clk = pllq.map(|pllq| pllsrcclk / pllm * plln / pllq);
+        let real_pllsysclk = match pllp {
+            Some(pllp) => Some(pllsrcclk / pllm * plln / pllp),
+            None => None,
+        };
+        let real_pll48clk = match pllq {
+            Some(pllq) => Some(pllsrcclk / pllm * plln / pllq),
+            None => None,
+        };
 
-        (
+        Ok((
             Self::Used {
                 pllsysclk: real_pllsysclk,
                 pll48clk: real_pll48clk,
                 m: pllm,
+                n: plln,
+                p: pllp,
+                q: pllq,
+                r: Some(pllr),
             },
             // TODO: check this
             None,
-        )
+        ))
+    }
+
+    /// Returns the "P", "Q" and "R" dividers for `vco_out` and their error
+    const fn optimize_dividers(
+        vco_out: u32,
+        pllsysclk: Option<u32>,
+        pll48clk: bool,
+        plli2sclk: u32,
+    ) -> Option<(Option<u32>, Option<u32>, u32, u32)> {
+        use super::{SYSCLK_MAX, SYSCLK_MIN};
+
+        // The "P" divider value must be even (2, 4, 6, 8).
+        let p = match pllsysclk {
+            Some(pllsysclk) => match Self::best_divider(
+                vco_out,
+                SYSCLK_MIN * 2,
+                pllsysclk * 2,
+                SYSCLK_MAX * 2,
+                1,
+                4,
+            ) {
+                Some((p, p_output, p_error)) => Some((p * 2, p_output / 2, p_error / 2)),
+                None => return None,
+            },
+            None => None,
+        };
+
+        // The 48 MHz clock must be accurate within 0.25% for USB.
+        let q = match Self::best_divider(vco_out, 47_880_000, 48_000_000, 48_120_000, 2, 15) {
+            Some(q) if pll48clk => Some(q),
+            Some(_) => None,
+            None => return None,
+        };
+
+        // We do not set any accuracy requirements for I2S, as on F410 this frequency is
+        // provided on a best-effort basis.
+        // TODO: What is the maximum valid input frequency for I2S?
+        let r = match Self::best_divider(vco_out, 0, plli2sclk, u32::MAX, 2, 15) {
+            Some(r) => r,
+            None => return None,
+        };
+
+        let p_error = match p {
+            Some((_, _, error)) => error,
+            None => 0,
+        };
+        let error = p_error + p_error + r.2;
+
+        Some((
+            match p {
+                Some((p, _, _)) => Some(p),
+                None => None,
+            },
+            match q {
+                Some((q, _, _)) => Some(q),
+                None => None,
+            },
+            r.0,
+            error,
+        ))
     }
 
     const fn best_divider(
@@ -393,107 +607,137 @@ impl MainPll {
     }
 }
 
+/// I2S PLL configuration
 #[cfg(not(feature = "gpio-f410"))]
-#[derive(Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum I2sPll {
     Used {
-        /// "M" divisor, required for the other PLLs on some MCUs.
-        m: u32,
         /// PLL I2S clock output.
         plli2sclk: u32,
+        config: SingleOutputPll,
     },
     Unused,
 }
 
 #[cfg(not(feature = "gpio-f410"))]
 impl I2sPll {
-    fn use_pll(&self) -> bool {
+    pub const fn use_pll(&self) -> bool {
         matches!(self, Self::Used { .. })
     }
 
+    /// "M" divisor, required for the other PLLs on some MCUs.
     #[allow(unused)]
-    fn m(&self) -> Option<u32> {
-        if let Self::Used { m, .. } = self {
-            Some(*m)
-        } else {
-            None
+    pub const fn m(&self) -> Option<u32> {
+        match self {
+            Self::Used { config, .. } => Some(config.m as u32),
+            Self::Unused => None,
         }
     }
 
-    fn plli2sclk(&self) -> Option<u32> {
-        if let Self::Used { plli2sclk, .. } = self {
-            Some(*plli2sclk)
-        } else {
-            None
+    pub const fn plli2sclk(&self) -> Option<u32> {
+        match self {
+            Self::Used { plli2sclk, .. } => Some(*plli2sclk),
+            Self::Unused => None,
         }
     }
 
-    fn setup(pllsrcclk: u32, plli2sclk: Option<u32>) -> Self {
-        let Some(target) = plli2sclk else {
-            return Self::Unused;
+    /// Finds the I2S PLL configuration closest to `plli2sclk`
+    pub const fn setup(pllsrcclk: u32, plli2sclk: Option<u32>) -> Result<Self, Error> {
+        let target = match plli2sclk {
+            Some(target) => target,
+            None => return Ok(Self::Unused),
         };
         // Input divisor from PLL source clock, must result to frequency in
         // the range from 1 to 2 MHz
         let pllm_min = (pllsrcclk + 1_999_999) / 2_000_000;
         let pllm_max = pllsrcclk / 1_000_000;
-        let (pll, config, _) = (pllm_min..=pllm_max)
-            .map(|m| Self::optimize_fixed_m(pllsrcclk, m, target))
-            .min_by_key(|(_, _, error)| *error)
-            .expect("no suitable I2S PLL configuration found");
-        Self::apply_config(config);
-        pll
+        let mut best = None;
+        let mut m = pllm_min;
+        while m <= pllm_max {
+            if let Some((pll, error)) = Self::optimize_fixed_m(pllsrcclk, m, target) {
+                best = match best {
+                    Some((_, best_error)) if best_error <= error => best,
+                    _ => Some((pll, error)),
+                };
+            }
+            m += 1;
+        }
+        match best {
+            Some((pll, _)) => Ok(pll),
+            None => Err(Error::Plli2sUnreachable),
+        }
     }
 
+    /// Finds the I2S PLL configuration closest to `plli2sclk` with "M" divisor of the main PLL
     #[cfg(feature = "rcc_shared_m")]
-    fn setup_shared_m(pllsrcclk: u32, m: Option<u32>, plli2sclk: Option<u32>) -> Self {
+    pub const fn setup_shared_m(
+        pllsrcclk: u32,
+        m: Option<u32>,
+        plli2sclk: Option<u32>,
+    ) -> Result<Self, Error> {
         // "m" is None if the main PLL is not in use.
-        let Some(m) = m else {
-            return Self::setup(pllsrcclk, plli2sclk);
+        let (m, target) = match (m, plli2sclk) {
+            (None, _) => return Self::setup(pllsrcclk, plli2sclk),
+            (Some(_), None) => return Ok(Self::Unused),
+            (Some(m), Some(target)) => (m, target),
         };
-        let Some(target) = plli2sclk else {
-            return Self::Unused;
-        };
-        let (pll, config, _) = Self::optimize_fixed_m(pllsrcclk, m, target);
-        Self::apply_config(config);
-        pll
+        match Self::optimize_fixed_m(pllsrcclk, m, target) {
+            Some((pll, _)) => Ok(pll),
+            None => Err(Error::Plli2sUnreachable),
+        }
     }
 
-    fn optimize_fixed_m(pllsrcclk: u32, m: u32, plli2sclk: u32) -> (Self, SingleOutputPll, u32) {
-        let (config, real_plli2sclk, error) =
-            SingleOutputPll::optimize(pllsrcclk, m, plli2sclk, 2, 7)
-                .expect("did not find any valid I2S PLL config");
-        (
-            Self::Used {
-                m: config.m as u32,
-                plli2sclk: real_plli2sclk,
-            },
-            config,
-            error,
-        )
+    const fn optimize_fixed_m(pllsrcclk: u32, m: u32, plli2sclk: u32) -> Option<(Self, u32)> {
+        match SingleOutputPll::optimize(pllsrcclk, m, plli2sclk, 2, 7) {
+            Some((config, real_plli2sclk, error)) => Some((
+                Self::Used {
+                    plli2sclk: real_plli2sclk,
+                    config,
+                },
+                error,
+            )),
+            None => None,
+        }
     }
+}
 
-    fn apply_config(config: SingleOutputPll) {
-        let rcc = unsafe { &*RCC::ptr() };
-        // "M" may have been written before, but the value is identical.
-        #[cfg(feature = "rcc_shared_m")]
-        rcc.pllcfgr()
-            .modify(|_, w| unsafe { w.pllm().bits(config.m) });
-        rcc.plli2scfgr().modify(|_, w| unsafe {
-            #[cfg(not(feature = "rcc_shared_m"))]
-            w.plli2sm().bits(config.m);
-            w.plli2sn().bits(config.n);
-            w.plli2sr().bits(config.outdiv)
-        });
+#[cfg(any(feature = "gpio-f412", feature = "gpio-f413"))]
+impl I2sPll {
+    /// Starts the PLL for the 48 MHz clock if it is not in use,
+    /// returns the "Q" divider and its output
+    pub const fn setup_clk48(self, pllsrcclk: u32) -> Result<(Self, u8, u32), Error> {
+        let pll = match self {
+            Self::Unused => match Self::setup(pllsrcclk, Some(48_000_000)) {
+                Ok(pll) => pll,
+                Err(e) => return Err(e),
+            },
+            used => used,
+        };
+        let vco_out = match pll {
+            Self::Used { config, .. } => pllsrcclk / config.m as u32 * config.n as u32,
+            Self::Unused => return Err(Error::Plli2sUnreachable),
+        };
+        let q = crate::min_u32(crate::max_u32((vco_out + 24_000_000) / 48_000_000, 2), 15);
+        Ok((pll, q as u8, vco_out / q))
     }
 }
 
+/// SAI PLL configuration
 #[cfg(feature = "sai")]
 #[cfg(not(feature = "gpio-f413"))]
-#[derive(Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum SaiPll {
     Used {
-        /// SAI clock (PLL output divided by the SAI clock divider).
-        sai_clk: u32,
+        /// SAI clock (PLL output divided by the SAI clock divider), if requested.
+        sai_clk: Option<u32>,
+        config: SingleOutputPll,
+        /// SAI clock divider
+        saidiv: u32,
+        /// LTDC clock generated from the "R" output, if requested.
+        #[cfg(feature = "ltdc")]
+        ltdc: Option<LtdcClock>,
     },
     Unused,
 }
@@ -501,101 +745,373 @@ pub enum SaiPll {
 #[cfg(feature = "sai")]
 #[cfg(not(feature = "gpio-f413"))]
 impl SaiPll {
-    fn use_pll(&self) -> bool {
+    pub const fn use_pll(&self) -> bool {
         matches!(self, Self::Used { .. })
     }
 
-    fn sai_clk(&self) -> Option<u32> {
-        if let Self::Used { sai_clk } = self {
-            Some(*sai_clk)
-        } else {
-            None
+    pub const fn sai_clk(&self) -> Option<u32> {
+        match self {
+            Self::Used { sai_clk, .. } => *sai_clk,
+            Self::Unused => None,
         }
     }
 
-    fn setup(pllsrcclk: u32, sai_clk: Option<u32>) -> SaiPll {
-        let Some(target) = sai_clk else {
-            return Self::Unused;
+    #[cfg(feature = "ltdc")]
+    pub const fn ltdc_clk(&self) -> Option<u32> {
+        match self {
+            Self::Used {
+                ltdc: Some(ltdc), ..
+            } => Some(ltdc.ltdc_clk),
+            _ => None,
+        }
+    }
+
+    /// Finds the SAI PLL and divider configuration closest to `sai_clk`
+    pub const fn setup(pllsrcclk: u32, sai_clk: Option<u32>) -> Result<SaiPll, Error> {
+        let target = match sai_clk {
+            Some(target) => target,
+            None => return Ok(Self::Unused),
         };
         // Input divisor from PLL source clock, must result to frequency in
         // the range from 1 to 2 MHz
         let pllm_min = (pllsrcclk + 1_999_999) / 2_000_000;
         let pllm_max = pllsrcclk / 1_000_000;
-        let (pll, config, saidiv, _) = (pllm_min..=pllm_max)
-            .map(|m| Self::optimize_fixed_m(pllsrcclk, m, target))
-            .min_by_key(|(_, _, _, error)| *error)
-            .expect("no suitable SAI PLL configuration found");
-        Self::apply_config(config, saidiv);
-        pll
+        let mut best = None;
+        let mut m = pllm_min;
+        while m <= pllm_max {
+            if let Some((pll, error)) = Self::optimize_fixed_m(pllsrcclk, m, target) {
+                best = match best {
+                    Some((_, best_error)) if best_error <= error => best,
+                    _ => Some((pll, error)),
+                };
+            }
+            m += 1;
+        }
+        match best {
+            Some((pll, _)) => Ok(pll),
+            None => Err(Error::PllsaiUnreachable),
+        }
     }
 
+    /// Finds the SAI PLL and divider configuration closest to `sai_clk` with a shared "M" divisor
     #[cfg(feature = "rcc_shared_m")]
-    fn setup_shared_m(pllsrcclk: u32, m: Option<u32>, sai_clk: Option<u32>) -> Self {
+    pub const fn setup_shared_m(
+        pllsrcclk: u32,
+        m: Option<u32>,
+        sai_clk: Option<u32>,
+    ) -> Result<Self, Error> {
         // "m" is None if both other PLLs are not in use.
-        let Some(m) = m else {
-            return Self::setup(pllsrcclk, sai_clk);
-        };
-        let Some(target) = sai_clk else {
-            return Self::Unused;
+        let (m, target) = match (m, sai_clk) {
+            (None, _) => return Self::setup(pllsrcclk, sai_clk),
+            (Some(_), None) => return Ok(Self::Unused),
+            (Some(m), Some(target)) => (m, target),
         };
-        let (pll, config, saidiv, _) = Self::optimize_fixed_m(pllsrcclk, m, target);
-        Self::apply_config(config, saidiv);
-        pll
+        match Self::optimize_fixed_m(pllsrcclk, m, target) {
+            Some((pll, _)) => Ok(pll),
+            None => Err(Error::PllsaiUnreachable),
+        }
     }
 
-    fn optimize_fixed_m(
-        pllsrcclk: u32,
-        m: u32,
-        sai_clk: u32,
-    ) -> (SaiPll, SingleOutputPll, u32, u32) {
+    const fn optimize_fixed_m(pllsrcclk: u32, m: u32, sai_clk: u32) -> Option<(SaiPll, u32)> {
         // NOTE: This code tests lots of configurations due to the nested loops for the two
         // dividers. A smarter approach can probably speed up the search.
-        let (config, saidiv, real_sai_clk, error) = (1..=32)
-            .filter_map(|saidiv| {
-                let target = sai_clk * saidiv;
-                let (config, real_sai_clk, error) =
-                    SingleOutputPll::optimize(pllsrcclk, m, target, 2, 15)?;
-                Some((config, saidiv, real_sai_clk, error))
-            })
-            .min_by_key(|(_, _, _, error)| *error)
-            .expect("no suitable I2S PLL configuration found");
-        (
-            Self::Used {
-                sai_clk: real_sai_clk,
-            },
-            config,
-            saidiv,
-            error,
-        )
+        let mut best = None;
+        let mut saidiv = 1;
+        while saidiv <= 32 {
+            let target = sai_clk * saidiv;
+            if let Some((config, real_pllsaiclk, error)) =
+                SingleOutputPll::optimize(pllsrcclk, m, target, 2, 15)
+            {
+                best = match best {
+                    Some((_, best_error)) if best_error <= error => best,
+                    _ => Some((
+                        Self::Used {
+                            sai_clk: Some(real_pllsaiclk / saidiv),
+                            config,
+                            saidiv,
+                            #[cfg(feature = "ltdc")]
+                            ltdc: None,
+                        },
+                        error,
+                    )),
+                };
+            }
+            saidiv += 1;
+        }
+        best
     }
+}
 
-    fn apply_config(config: SingleOutputPll, saidiv: u32) {
-        let rcc = unsafe { &*RCC::ptr() };
-        rcc.dckcfgr()
-            .modify(|_, w| w.pllsaidivq().set(saidiv as u8 - 1));
-        // "M" may have been written before, but the value is identical.
-        #[cfg(feature = "rcc_shared_m")]
-        rcc.pllcfgr()
-            .modify(|_, w| unsafe { w.pllm().bits(config.m) });
-        rcc.pllsaicfgr().modify(|_, w| unsafe {
-            #[cfg(not(feature = "rcc_shared_m"))]
-            w.pllsaim().bits(config.m);
-            w.pllsain().bits(config.n);
-            w.pllsaiq().bits(config.outdiv)
-        });
+#[cfg(any(feature = "gpio-f446", feature = "gpio-f469"))]
+impl SaiPll {
+    /// Starts the PLL for the 48 MHz clock if it is not in use,
+    /// returns the "P" divider and its output
+    ///
+    /// `m` is the shared "M" divisor if another PLL is in use.
+    pub const fn setup_clk48(
+        self,
+        pllsrcclk: u32,
+        m: Option<u32>,
+    ) -> Result<(Self, u8, u32), Error> {
+        let pll = match self {
+            Self::Unused => {
+                // Input divisor from PLL source clock, must result to frequency in
+                // the range from 1 to 2 MHz
+                let (pllm_min, pllm_max) = match m {
+                    Some(m) => (m, m),
+                    None => ((pllsrcclk + 1_999_999) / 2_000_000, pllsrcclk / 1_000_000),
+                };
+                let mut best: Option<(SingleOutputPll, u32)> = None;
+                let mut m = pllm_min;
+                while m <= pllm_max {
+                    let vco_in = pllsrcclk / m;
+                    // "P" divider must be 2, 4, 6 or 8
+                    let mut p = 2;
+                    while p <= 8 {
+                        let n = (48_000_000 * p + (vco_in >> 1)) / vco_in;
+                        let vco_out = vco_in * n;
+                        if 100_000_000 <= vco_out && vco_out <= 432_000_000 {
+                            let error = (vco_out / p).abs_diff(48_000_000);
+                            best = match best {
+                                Some((_, best_error)) if best_error <= error => best,
+                                _ => Some((
+                                    SingleOutputPll {
+                                        m: m as u8,
+                                        n: n as u16,
+                                        // "Q" output is not used
+                                        outdiv: 2,
+                                    },
+                                    error,
+                                )),
+                            };
+                        }
+                        p += 2;
+                    }
+                    m += 1;
+                }
+                match best {
+                    Some((config, _)) => Self::Used {
+                        sai_clk: None,
+                        config,
+                        saidiv: 1,
+                        #[cfg(feature = "ltdc")]
+                        ltdc: None,
+                    },
+                    None => return Err(Error::PllsaiUnreachable),
+                }
+            }
+            used => used,
+        };
+        let vco_out = match pll {
+            Self::Used { config, .. } => pllsrcclk / config.m as u32 * config.n as u32,
+            Self::Unused => return Err(Error::PllsaiUnreachable),
+        };
+        let p = Self::clk48_divider(vco_out);
+        Ok((pll, p as u8, vco_out / p))
+    }
+
+    /// Returns the "P" divider closest to 48 MHz for `vco_out`
+    const fn clk48_divider(vco_out: u32) -> u32 {
+        let mut best_p = 2;
+        let mut p = 4;
+        while p <= 8 {
+            if (vco_out / p).abs_diff(48_000_000) < (vco_out / best_p).abs_diff(48_000_000) {
+                best_p = p;
+            }
+            p += 2;
+        }
+        best_p
+    }
+}
+
+/// Maximum deviation of the LTDC clock from the requested frequency, 1 %
+#[cfg(feature = "ltdc")]
+const LTDC_TOLERANCE_DIV: u32 = 100;
+
+/// Maximum deviation of the SAI clock when PLLSAI also generates the LTDC clock, 0.1 %
+#[cfg(feature = "ltdc")]
+const SAI_TOLERANCE_DIV: u32 = 1000;
+
+#[cfg(feature = "ltdc")]
+impl SaiPll {
+    /// Finds a PLLSAI configuration generating both `sai_clk` and `ltdc_clk`
+    ///
+    /// `m` is the shared "M" divisor if another PLL is in use. Returns `None` if no
+    /// configuration keeps the LTDC clock within 1 % and the SAI clock within 0.1 % (or the
+    /// accuracy it has without LTDC, if that is worse) of the requested frequencies.
+    /// If `clk48` is set, the "P" output must also generate a valid 48 MHz clock.
+    pub const fn setup_with_ltdc(
+        pllsrcclk: u32,
+        m: Option<u32>,
+        sai_clk: Option<u32>,
+        ltdc_clk: u32,
+        clk48: bool,
+    ) -> Option<Self> {
+        // Input divisor from PLL source clock, must result to frequency in
+        // the range from 1 to 2 MHz
+        let (pllm_min, pllm_max) = match m {
+            Some(m) => (m, m),
+            None => ((pllsrcclk + 1_999_999) / 2_000_000, pllsrcclk / 1_000_000),
+        };
+
+        let sai_limit = match sai_clk {
+            Some(target) => {
+                let mut limit = target / SAI_TOLERANCE_DIV;
+                let mut m = pllm_min;
+                while m <= pllm_max {
+                    if let Some((pll, _)) = Self::optimize_fixed_m(pllsrcclk, m, target) {
+                        if let Some(real) = pll.sai_clk() {
+                            limit = crate::max_u32(limit, real.abs_diff(target));
+                        }
+                    }
+                    m += 1;
+                }
+                limit
+            }
+            None => 0,
+        };
+        let ltdc_limit = ltdc_clk / LTDC_TOLERANCE_DIV;
+
+        let mut best: Option<(SaiPll, u32, u32)> = None;
+        let mut m = pllm_min;
+        while m <= pllm_max {
+            let vco_in = pllsrcclk / m;
+            // VCO output must be in the range from 100 to 432 MHz
+            let mut n = (100_000_000 + vco_in - 1) / vco_in;
+            while vco_in * n <= 432_000_000 {
+                let vco_out = vco_in * n;
+                let (ltdc, ltdc_error) = LtdcClock::optimize(vco_out, ltdc_clk);
+                let (q, saidiv, real_sai_clk, sai_error) = match sai_clk {
+                    Some(target) => {
+                        let (q, saidiv, real) = Self::optimize_dividers(vco_out, target);
+                        (q, saidiv, Some(real), real.abs_diff(target))
+                    }
+                    None => (2, 1, None, 0),
+                };
+                // STM32F429/439 have no "P" output
+                #[cfg(feature = "gpio-f469")]
+                let clk48_valid = !clk48
+                    || super::is_pll48clk_valid(Some(vco_out / Self::clk48_divider(vco_out)));
+                #[cfg(not(feature = "gpio-f469"))]
+                let clk48_valid = !clk48;
+                if ltdc_error <= ltdc_limit && sai_error <= sai_limit && clk48_valid {
+                    best = match best {
+                        Some((_, best_sai_error, best_ltdc_error))
+                            if best_sai_error < sai_error
+                                || best_sai_error == sai_error && best_ltdc_error <= ltdc_error =>
+                        {
+                            best
+                        }
+                        _ => Some((
+                            Self::Used {
+                                sai_clk: real_sai_clk,
+                                config: SingleOutputPll {
+                                    m: m as u8,
+                                    n: n as u16,
+                                    outdiv: q,
+                                },
+                                saidiv,
+                                ltdc: Some(ltdc),
+                            },
+                            sai_error,
+                            ltdc_error,
+                        )),
+                    };
+                }
+                n += 1;
+            }
+            m += 1;
+        }
+        match best {
+            Some((pll, _, _)) => Some(pll),
+            None => None,
+        }
+    }
+
+    /// Returns the "Q" divider, the SAI clock divider and the SAI clock closest to `sai_clk`
+    const fn optimize_dividers(vco_out: u32, sai_clk: u32) -> (u8, u32, u32) {
+        let mut best = (2, 1, 0);
+        let mut best_error = u32::MAX;
+        let mut q = 2;
+        while q <= 15 {
+            let pllsaiclk = vco_out / q;
+            // Try the SAI dividers next to the exact ratio
+            let div = crate::min_u32(crate::max_u32(pllsaiclk / sai_clk, 1), 32);
+            let mut saidiv = div;
+            while saidiv <= crate::min_u32(div + 1, 32) {
+                let real = pllsaiclk / saidiv;
+                let error = real.abs_diff(sai_clk);
+                if error < best_error {
+                    best = (q as u8, saidiv, real);
+                    best_error = error;
+                }
+                saidiv += 1;
+            }
+            q += 1;
+        }
+        best
+    }
+}
+
+/// LTDC clock generated by PLLSAI
+#[cfg(feature = "ltdc")]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct LtdcClock {
+    /// LTDC clock ("R" output divided by the LTDC clock divider)
+    pub ltdc_clk: u32,
+    /// "R" output divider, 2 to 7
+    pub r: u8,
+    /// LTDC clock divider, 2, 4, 8 or 16
+    pub divr: u8,
+}
+
+#[cfg(feature = "ltdc")]
+impl LtdcClock {
+    /// Returns the dividers closest to `ltdc_clk` and the error
+    const fn optimize(vco_out: u32, ltdc_clk: u32) -> (Self, u32) {
+        let mut best = LtdcClock {
+            ltdc_clk: 0,
+            r: 2,
+            divr: 2,
+        };
+        let mut best_error = u32::MAX;
+        let mut r = 2;
+        while r <= 7 {
+            let mut divr = 2;
+            while divr <= 16 {
+                let clk = vco_out / r / divr;
+                let error = clk.abs_diff(ltdc_clk);
+                if error < best_error {
+                    best = LtdcClock {
+                        ltdc_clk: clk,
+                        r: r as u8,
+                        divr: divr as u8,
+                    };
+                    best_error = error;
+                }
+                divr *= 2;
+            }
+            r += 1;
+        }
+        (best, best_error)
     }
 }
 
+/// Configuration of a PLL with one used output
 #[cfg(not(feature = "gpio-f410"))]
-struct SingleOutputPll {
-    m: u8,
-    n: u16,
-    outdiv: u8,
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct SingleOutputPll {
+    pub m: u8,
+    pub n: u16,
+    pub outdiv: u8,
 }
 
 #[cfg(not(feature = "gpio-f410"))]
 impl SingleOutputPll {
-    fn optimize(
+    /// Returns the configuration, its output and the error from `target`
+    pub const fn optimize(
         pllsrcclk: u32,
         m: u32,
         target: u32,
@@ -606,30 +1122,31 @@ impl SingleOutputPll {
 
         // We loop through the possible divider values to find the best configuration. Looping
         // through all possible "N" values would result in more iterations.
-        let (n, outdiv, output, error) = (min_div..=max_div)
-            .filter_map(|outdiv| {
-                let target_vco_out = match target.checked_mul(outdiv) {
-                    Some(x) => x,
-                    None => return None,
-                };
+        let mut best: Option<(SingleOutputPll, u32, u32)> = None;
+        let mut outdiv = min_div;
+        while outdiv <= max_div {
+            if let Some(target_vco_out) = target.checked_mul(outdiv) {
                 let n = (target_vco_out + (vco_in >> 1)) / vco_in;
                 let vco_out = vco_in * n;
-                if !(100_000_000..=432_000_000).contains(&vco_out) {
-                    return None;
+                if 100_000_000 <= vco_out && vco_out <= 432_000_000 {
+                    let output = vco_out / outdiv;
+                    let error = (output as i32 - target as i32).unsigned_abs();
+                    best = match best {
+                        Some((_, _, best_error)) if best_error <= error => best,
+                        _ => Some((
+                            SingleOutputPll {
+                                m: m as u8,
+                                n: n as u16,
+                                outdiv: outdiv as u8,
+                            },
+                            output,
+                            error,
+                        )),
+                    };
                 }
-                let output = vco_out / outdiv;
-                let error = (output as i32 - target as i32).unsigned_abs();
-                Some((n, outdiv, output, error))
-            })
-            .min_by_key(|(_, _, _, error)| *error)?;
-        Some((
-            SingleOutputPll {
-                m: m as u8,
-                n: n as u16,
-                outdiv: outdiv as u8,
-            },
-            output,
-            error,
-        ))
+            }
+            outdiv += 1;
+        }
+        best
     }
 }
@@ -0,0 +1,230 @@
+use super::*;
+
+const HSE: Hertz = Hertz::MHz(8);
+
+/// Computed at compile time
+const PLAN_84MHZ: Result<ClockPlan, Error> = CFGR::new()
+    .use_hse(HSE)
+    .sysclk(Hertz::MHz(84))
+    .require_pll48clk()
+    .solve();
+
+#[test]
+fn hsi_without_pll() {
+    let plan = CFGR::new().solve().unwrap();
+    let clocks = plan.clocks();
+
+    assert!(!plan.plls().use_pll);
+    assert_eq!(clocks.sysclk(), Hertz::from_raw(HSI));
+    assert_eq!(clocks.hclk(), Hertz::from_raw(HSI));
+    assert_eq!(clocks.pclk1(), Hertz::from_raw(HSI));
+    assert_eq!(clocks.pclk2(), Hertz::from_raw(HSI));
+    assert_eq!(clocks.pll48clk(), None);
+    assert_eq!(plan.sysclk_error(), None);
+    assert_eq!(plan.flash_latency, 0);
+}
+
+#[test]
+fn hse_without_pll() {
+    let plan = CFGR::new().use_hse(HSE).solve().unwrap();
+
+    assert!(!plan.plls().use_pll);
+    assert_eq!(plan.clocks().sysclk(), HSE);
+}
+
+#[test]
+fn const_plan() {
+    let plan = PLAN_84MHZ.unwrap();
+    let clocks = plan.clocks();
+
+    assert!(plan.plls().use_pll);
+    assert_eq!(clocks.sysclk(), Hertz::MHz(84));
+    assert_eq!(clocks.pll48clk(), Some(Hertz::MHz(48)));
+    assert!(clocks.is_pll48clk_valid());
+    assert_eq!(plan.sysclk_error(), Some(0));
+    assert_eq!(plan.pll48clk_error(), Some(0));
+}
+
+#[test]
+fn max_sysclk() {
+    let plan = CFGR::new()
+        .use_hse(HSE)
+        .sysclk(Hertz::from_raw(SYSCLK_MAX))
+        .solve()
+        .unwrap();
+    let clocks = plan.clocks();
+
+    assert_eq!(clocks.sysclk().raw(), SYSCLK_MAX);
+    assert_eq!(clocks.hclk().raw(), SYSCLK_MAX);
+    assert!(clocks.pclk1().raw() <= PCLK1_MAX);
+    assert!(clocks.pclk2().raw() <= PCLK2_MAX);
+    assert!(plan.flash_latency as u32 <= FLASH_LATENCY_MAX);
+}
+
+#[test]
+fn bus_prescalers() {
+    let plan = CFGR::new()
+        .use_hse(HSE)
+        .sysclk(Hertz::MHz(84))
+        .pclk1(Hertz::MHz(21))
+        .pclk2(Hertz::MHz(42))
+        .solve()
+        .unwrap();
+    let clocks = plan.clocks();
+
+    assert_eq!(clocks.hclk(), Hertz::MHz(84));
+    assert_eq!(clocks.pclk1(), Hertz::MHz(21));
+    assert_eq!(clocks.timclk1(), Hertz::MHz(42));
+    assert_eq!(clocks.pclk2(), Hertz::MHz(42));
+    assert_eq!(clocks.timclk2(), Hertz::MHz(84));
+    assert_eq!(plan.ppre1_bits, 0b101);
+    assert_eq!(plan.ppre2_bits, 0b100);
+}
+
+#[test]
+fn overclocking() {
+    let cfgr = CFGR::new()
+        .use_hse(HSE)
+        .sysclk(Hertz::from_raw(SYSCLK_MAX + 20_000_000));
+
+    assert_eq!(cfgr.solve(), Err(Error::SysclkOutOfRange));
+    assert!(cfgr.solve_internal(true).is_ok());
+}
+
+#[test]
+fn unreachable_sysclk() {
+    let cfgr = CFGR::new()
+        .use_hse(HSE)
+        .sysclk(Hertz::from_raw(PLL_SYSCLK_MAX + 1));
+
+    assert_eq!(cfgr.solve(), Err(Error::SysclkUnreachable));
+}
+
+#[test]
+fn i2s_ckin() {
+    let cfgr = CFGR::new()
+        .use_hse(HSE)
+        .sysclk(Hertz::MHz(84))
+        .i2s_ckin(Hertz::kHz(12_288));
+    #[cfg(not(feature = "rcc_i2s_apb"))]
+    let cfgr = cfgr.i2s_clk(Hertz::kHz(12_288));
+    #[cfg(feature = "rcc_i2s_apb")]
+    let cfgr = cfgr.i2s_apb1_clk(Hertz::kHz(12_288));
+    let plan = cfgr.solve().unwrap();
+
+    #[cfg(not(feature = "rcc_i2s_apb"))]
+    assert_eq!(plan.clocks().i2s_clk(), Some(Hertz::kHz(12_288)));
+    #[cfg(feature = "rcc_i2s_apb")]
+    assert_eq!(plan.clocks().i2s_apb1_clk(), Some(Hertz::kHz(12_288)));
+    #[cfg(not(feature = "gpio-f410"))]
+    assert!(!plan.plls().use_i2spll);
+}
+
+#[cfg(any(feature = "gpio-f410", feature = "rcc_i2s_apb"))]
+#[test]
+fn i2s_from_pll_source() {
+    let cfgr = CFGR::new().use_hse(HSE);
+    #[cfg(not(feature = "rcc_i2s_apb"))]
+    let cfgr = cfgr.i2s_clk(Hertz::MHz(96)).i2s_clk_src(I2sClkSrc::PllSrc);
+    #[cfg(feature = "rcc_i2s_apb")]
+    let cfgr = cfgr
+        .i2s_apb1_clk(Hertz::MHz(96))
+        .i2s_apb1_clk_src(I2sClkSrc::PllSrc);
+    let plan = cfgr.solve().unwrap();
+
+    assert!(!plan.plls().use_pll);
+    #[cfg(not(feature = "rcc_i2s_apb"))]
+    assert_eq!(plan.clocks().i2s_clk(), Some(HSE));
+    #[cfg(feature = "rcc_i2s_apb")]
+    assert_eq!(plan.clocks().i2s_apb1_clk(), Some(HSE));
+    #[cfg(not(feature = "gpio-f410"))]
+    assert!(!plan.plls().use_i2spll);
+}
+
+#[cfg(not(feature = "gpio-f410"))]
+#[test]
+fn i2s_pll() {
+    let cfgr = CFGR::new().use_hse(HSE).sysclk(Hertz::MHz(84));
+    #[cfg(not(feature = "rcc_i2s_apb"))]
+    let cfgr = cfgr.i2s_clk(Hertz::MHz(96));
+    #[cfg(feature = "rcc_i2s_apb")]
+    let cfgr = cfgr.i2s_apb1_clk(Hertz::MHz(96));
+    let plan = cfgr.solve().unwrap();
+
+    assert!(plan.plls().use_i2spll);
+    #[cfg(not(feature = "rcc_i2s_apb"))]
+    assert_eq!(plan.i2s_clk_error(), Some(0));
+    #[cfg(feature = "rcc_i2s_apb")]
+    assert_eq!(plan.i2s_apb1_clk_error(), Some(0));
+}
+
+#[cfg(feature = "sai")]
+#[test]
+fn sai_clk() {
+    let cfgr = CFGR::new().use_hse(HSE).sysclk(Hertz::MHz(84));
+    #[cfg(not(feature = "sai2"))]
+    let plan = cfgr.saia_clk(Hertz::MHz(12)).solve().unwrap();
+    #[cfg(feature = "sai2")]
+    let plan = cfgr.sai1_clk(Hertz::MHz(12)).solve().unwrap();
+
+    #[cfg(not(feature = "sai2"))]
+    assert_eq!(plan.clocks().saia_clk(), Some(Hertz::MHz(12)));
+    #[cfg(feature = "sai2")]
+    assert_eq!(plan.clocks().sai1_clk(), Some(Hertz::MHz(12)));
+}
+
+#[cfg(feature = "ltdc")]
+#[test]
+fn ltdc_clk() {
+    let plan = CFGR::new()
+        .use_hse(HSE)
+        .sysclk(Hertz::MHz(84))
+        .saia_clk(Hertz::MHz(12))
+        .ltdc_clk(Hertz::MHz(12))
+        .solve()
+        .unwrap();
+
+    assert_eq!(plan.clocks().saia_clk(), Some(Hertz::MHz(12)));
+    assert_eq!(plan.clocks().ltdc_clk(), Some(Hertz::MHz(12)));
+}
+
+#[cfg(any(
+    feature = "gpio-f412",
+    feature = "gpio-f413",
+    feature = "gpio-f446",
+    feature = "gpio-f469"
+))]
+#[test]
+fn clk48_without_main_pll() {
+    #[cfg(any(feature = "gpio-f412", feature = "gpio-f413"))]
+    let src = Clk48Src::Plli2s;
+    #[cfg(any(feature = "gpio-f446", feature = "gpio-f469"))]
+    let src = Clk48Src::Pllsai;
+    let plan = CFGR::new()
+        .use_hse(HSE)
+        .sysclk(Hertz::from_raw(SYSCLK_MAX))
+        .require_pll48clk()
+        .clk48_src(src)
+        .solve()
+        .unwrap();
+
+    assert!(plan.clocks().is_pll48clk_valid());
+}
+
+#[cfg(feature = "gpio-f469")]
+#[test]
+fn ltdc_clk48_on_pllsai() {
+    let plan = CFGR::new()
+        .use_hse(HSE)
+        .sysclk(Hertz::from_raw(SYSCLK_MAX))
+        .require_pll48clk()
+        .clk48_src(Clk48Src::Pllsai)
+        .saia_clk(Hertz::MHz(12))
+        .ltdc_clk(Hertz::MHz(12))
+        .solve()
+        .unwrap();
+
+    assert!(plan.clocks().is_pll48clk_valid());
+    assert_eq!(plan.clocks().saia_clk(), Some(Hertz::MHz(12)));
+    assert_eq!(plan.clocks().ltdc_clk(), Some(Hertz::MHz(12)));
+}
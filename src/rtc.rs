@@ -10,6 +10,9 @@ use core::fmt;
 use fugit::RateExtU32;
 use time::{Date, PrimitiveDateTime, Time, Weekday};
 
+mod backup;
+pub use backup::{BackupKv, BackupValue, KvError, BACKUP_REGISTERS};
+
 /// Invalid input error
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
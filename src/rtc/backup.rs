@@ -0,0 +1,214 @@
+//! RTC backup registers
+//!
+//! Twenty 32-bit registers in the backup domain. Their content survives system
+//! resets, Standby mode and VBAT-only periods, and is cleared by a backup domain
+//! reset or a tamper event.
+//!
+//! ```
+//! use stm32f4xx_hal::{pac, rtc::Rtc};
+//!
+//! const UPDATE_STATE: u32 = 1;
+//!
+//! let mut dp = pac::Peripherals::take().unwrap();
+//! let mut rtc = Rtc::new(dp.RTC, &mut dp.PWR);
+//!
+//! rtc.write_backup(0, 0xB007_F1A6_u32);
+//! let calibration: i16 = rtc.read_backup(1);
+//!
+//! // Registers 0 and 1 are used above, the key-value storage takes the others
+//! let mut kv = rtc.backup_kv(2..20);
+//! kv.set(UPDATE_STATE, 2).unwrap();
+//! ```
+
+use core::ops::Range;
+
+use super::Rtc;
+
+/// Number of backup registers
+pub const BACKUP_REGISTERS: usize = 20;
+
+/// Value which can be stored in a backup register
+pub trait BackupValue: Sized {
+    /// Converts from the register content
+    fn from_backup(bits: u32) -> Self;
+    /// Converts to the register content
+    fn to_backup(self) -> u32;
+}
+
+macro_rules! backup_value {
+    ($($T:ty => $U:ty,)+) => {
+        $(
+            impl BackupValue for $T {
+                #[inline(always)]
+                fn from_backup(bits: u32) -> Self {
+                    bits as $U as Self
+                }
+                #[inline(always)]
+                fn to_backup(self) -> u32 {
+                    self as $U as u32
+                }
+            }
+        )+
+    };
+}
+
+backup_value! {
+    u8 => u8,
+    u16 => u16,
+    u32 => u32,
+    i8 => u8,
+    i16 => u16,
+    i32 => u32,
+}
+
+impl BackupValue for bool {
+    #[inline(always)]
+    fn from_backup(bits: u32) -> Self {
+        bits != 0
+    }
+    #[inline(always)]
+    fn to_backup(self) -> u32 {
+        self as u32
+    }
+}
+
+impl BackupValue for f32 {
+    #[inline(always)]
+    fn from_backup(bits: u32) -> Self {
+        f32::from_bits(bits)
+    }
+    #[inline(always)]
+    fn to_backup(self) -> u32 {
+        self.to_bits()
+    }
+}
+
+impl Rtc {
+    /// Reads backup register `index`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not lower than [`BACKUP_REGISTERS`].
+    pub fn read_backup_register(&self, index: usize) -> u32 {
+        assert!(index < BACKUP_REGISTERS);
+        self.regs.bkpr(index).read().bits()
+    }
+
+    /// Writes `value` to backup register `index`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not lower than [`BACKUP_REGISTERS`].
+    pub fn write_backup_register(&mut self, index: usize, value: u32) {
+        assert!(index < BACKUP_REGISTERS);
+        self.regs.bkpr(index).write(|w| unsafe { w.bits(value) });
+    }
+
+    /// Reads backup register `index` as `T`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not lower than [`BACKUP_REGISTERS`].
+    pub fn read_backup<T: BackupValue>(&self, index: usize) -> T {
+        T::from_backup(self.read_backup_register(index))
+    }
+
+    /// Writes `value` of type `T` to backup register `index`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not lower than [`BACKUP_REGISTERS`].
+    pub fn write_backup<T: BackupValue>(&mut self, index: usize, value: T) {
+        self.write_backup_register(index, value.to_backup())
+    }
+
+    /// Returns key-value storage using backup registers in `range`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` ends above [`BACKUP_REGISTERS`].
+    pub fn backup_kv(&mut self, range: Range<usize>) -> BackupKv<'_> {
+        assert!(range.end <= BACKUP_REGISTERS);
+        BackupKv { rtc: self, range }
+    }
+}
+
+/// Backup key-value storage error
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum KvError {
+    /// Key 0 marks free entries and can not be used
+    InvalidKey,
+    /// All entries are used
+    Full,
+}
+
+/// Key-value storage in backup registers
+///
+/// Each entry takes two registers of its range, a non-zero key followed by its value.
+/// Entries with key 0 are free, which is the state after a backup domain reset.
+pub struct BackupKv<'a> {
+    rtc: &'a mut Rtc,
+    range: Range<usize>,
+}
+
+impl BackupKv<'_> {
+    /// Maximum number of entries
+    pub fn capacity(&self) -> usize {
+        self.range.len() / 2
+    }
+
+    /// Returns the index of the key register of the entry with `key`
+    fn find(&self, key: u32) -> Option<usize> {
+        self.range
+            .clone()
+            .step_by(2)
+            .take(self.capacity())
+            .find(|&i| self.rtc.read_backup_register(i) == key)
+    }
+
+    /// Returns the value stored for `key`
+    pub fn get(&self, key: u32) -> Option<u32> {
+        if key == 0 {
+            return None;
+        }
+        self.find(key).map(|i| self.rtc.read_backup_register(i + 1))
+    }
+
+    /// Returns the value stored for `key` as `T`
+    pub fn get_as<T: BackupValue>(&self, key: u32) -> Option<T> {
+        self.get(key).map(T::from_backup)
+    }
+
+    /// Stores `value` for `key`, replacing the previous value
+    pub fn set(&mut self, key: u32, value: impl BackupValue) -> Result<(), KvError> {
+        if key == 0 {
+            return Err(KvError::InvalidKey);
+        }
+        let i = self
+            .find(key)
+            .or_else(|| self.find(0))
+            .ok_or(KvError::Full)?;
+        // Value is written first, so a reset in between never exposes a new key with a stale value
+        self.rtc.write_backup_register(i + 1, value.to_backup());
+        self.rtc.write_backup_register(i, key);
+        Ok(())
+    }
+
+    /// Removes `key`, returns its value
+    pub fn remove(&mut self, key: u32) -> Option<u32> {
+        if key == 0 {
+            return None;
+        }
+        let i = self.find(key)?;
+        self.rtc.write_backup_register(i, 0);
+        Some(self.rtc.read_backup_register(i + 1))
+    }
+
+    /// Removes all entries
+    pub fn clear(&mut self) {
+        for i in self.range.clone() {
+            self.rtc.write_backup_register(i, 0);
+        }
+    }
+}
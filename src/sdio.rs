@@ -109,6 +109,9 @@ pub enum Buswidth {
 }
 
 /// Clock frequency of a SDIO bus.
+///
+/// The values are dividers of a 48 MHz SDIO clock, they are scaled to the real SDIO clock.
+#[derive(Clone, Copy)]
 pub enum ClockFreq {
     F24Mhz = 0,
     F16Mhz = 1,
@@ -145,6 +148,7 @@ pub struct Sdio<P: SdioPeripheral> {
     bw: Buswidth,
     card: Option<P>,
     clock: Hertz,
+    sdio_clk: Hertz,
 }
 
 /// Sd card peripheral
@@ -168,6 +172,9 @@ pub struct Emmc {
 impl<P: SdioPeripheral> Sdio<P> {
     /// Create and enable the Sdio device
     pub fn new<PINS: Pins>(sdio: SDIO, pins: PINS, clocks: &Clocks) -> Self {
+        // Without a known kernel clock the dividers are used unscaled, as for a 48 MHz clock
+        let sdio_clk = clocks.sdio_clk().unwrap_or(Hertz::MHz(48));
+
         unsafe {
             // Enable and reset the sdio peripheral, it's the same bit position for both registers
             SDIO::enable_unchecked();
@@ -178,7 +185,7 @@ impl<P: SdioPeripheral> Sdio<P> {
         sdio.clkcr().write(|w| {
             w.widbus().bus_width1();
             w.clken().enabled();
-            w.clkdiv().set(ClockFreq::F400Khz as u8);
+            w.clkdiv().set(clkdiv(sdio_clk, ClockFreq::F400Khz));
             w.pwrsav().disabled();
             w.bypass().disabled();
             w.negedge().rising();
@@ -197,6 +204,7 @@ impl<P: SdioPeripheral> Sdio<P> {
             bw: PINS::BUSWIDTH,
             card: None,
             clock: clocks.sysclk(),
+            sdio_clk,
         };
 
         // Make sure card is powered off
@@ -634,7 +642,7 @@ impl Sdio<SdCard> {
         self.app_cmd(sd_cmd::set_bus_width(width == WIDBUS::BusWidth4))?;
 
         self.sdio.clkcr().modify(|_, w| {
-            w.clkdiv().set(freq as u8);
+            w.clkdiv().set(clkdiv(self.sdio_clk, freq));
             w.widbus().variant(width);
             w.clken().enabled()
         });
@@ -728,7 +736,7 @@ impl Sdio<Emmc> {
         // CMD6 is R1b, so wait for the card to be ready again before proceeding.
         while !self.card_ready()? {}
         self.sdio.clkcr().modify(|_, w| {
-            w.clkdiv().set(freq as u8);
+            w.clkdiv().set(clkdiv(self.sdio_clk, freq));
             w.widbus().variant(width);
             w.clken().enabled()
         });
@@ -736,6 +744,13 @@ impl Sdio<Emmc> {
     }
 }
 
+/// Returns the divider giving at most `freq` from `sdio_clk`
+fn clkdiv(sdio_clk: Hertz, freq: ClockFreq) -> u8 {
+    // SDIO_CK = SDIOCLK / (CLKDIV + 2)
+    let div = (sdio_clk.raw() / 1000 * (freq as u32 + 2) + 47_999) / 48_000;
+    crate::min_u32(div.saturating_sub(2), 255) as u8
+}
+
 fn status_to_error(sta: pac::sdio::sta::R) -> Result<(), Error> {
     if sta.ctimeout().bit_is_set() {
         return Err(Error::Timeout);
@@ -39,6 +39,11 @@ use crate::dma::{
     MemoryToPeripheral, PeripheralToMemory,
 };
 
+#[cfg(feature = "async")]
+mod asynch;
+#[cfg(feature = "async")]
+pub use asynch::{AsyncRx, AsyncTx};
+
 /// Serial error kind
 ///
 /// This represents a common set of serial operation errors. HAL implementations are
@@ -58,6 +63,8 @@ pub enum Error {
     Noise,
     /// A different error occurred. The original error may contain more information.
     Other,
+    /// DMA transfer error
+    Dma,
 }
 
 /// UART interrupt events
@@ -147,6 +154,10 @@ pub trait Instance:
     fn peri_address() -> u32 {
         unsafe { &*Self::ptr() }.peri_address()
     }
+
+    #[cfg(feature = "async")]
+    #[doc(hidden)]
+    fn wakers() -> &'static asynch::Wakers;
 }
 
 /// Trait for [`Rx`] interrupt handling.
@@ -211,6 +222,7 @@ pub struct Rx<USART: CommonPins, WORD = u8> {
     _word: PhantomData<WORD>,
     usart: USART,
     pin: USART::Rx<PushPull>,
+    baud: u32,
 }
 
 /// Serial transmitter containing TX pin
@@ -218,6 +230,7 @@ pub struct Tx<USART: CommonPins, WORD = u8> {
     _word: PhantomData<WORD>,
     usart: USART,
     pin: USART::Tx<PushPull>,
+    baud: u32,
 }
 
 pub trait SerialExt: Sized + Instance {
@@ -318,12 +331,64 @@ impl<USART: Instance, WORD> Serial<USART, WORD> {
         uart.enable_dma(config.dma);
 
         let serial = Serial {
-            tx: Tx::new(uart, pins.0.into()),
-            rx: Rx::new(unsafe { USART::steal() }, pins.1.into()),
+            tx: Tx::new(uart, pins.0.into(), baud),
+            rx: Rx::new(unsafe { USART::steal() }, pins.1.into(), baud),
         };
         serial.tx.usart.set_stopbits(config.stopbits);
         Ok(serial)
     }
+
+    /// Recomputes the baud rate divider and the IrDA low-power prescaler from `clocks`
+    /// returned by [`CFGR::refreeze`](crate::rcc::CFGR::refreeze).
+    pub fn configure(&mut self, clocks: &Clocks) -> Result<(), config::InvalidConfig> {
+        self.tx.configure(clocks)
+    }
+}
+
+impl<USART: Instance, WORD> Tx<USART, WORD> {
+    /// Recomputes the baud rate divider and the IrDA low-power prescaler from `clocks`
+    /// returned by [`CFGR::refreeze`](crate::rcc::CFGR::refreeze).
+    ///
+    /// Waits for the current transmission to complete. Both are shared with [`Rx`].
+    pub fn configure(&mut self, clocks: &Clocks) -> Result<(), config::InvalidConfig> {
+        set_baudrate(&self.usart, self.baud, clocks)
+    }
+}
+
+impl<USART: Instance, WORD> Rx<USART, WORD> {
+    /// Recomputes the baud rate divider and the IrDA low-power prescaler from `clocks`
+    /// returned by [`CFGR::refreeze`](crate::rcc::CFGR::refreeze).
+    ///
+    /// Both are shared with [`Tx`].
+    pub fn configure(&mut self, clocks: &Clocks) -> Result<(), config::InvalidConfig> {
+        set_baudrate(&self.usart, self.baud, clocks)
+    }
+}
+
+fn set_baudrate<USART: Instance>(
+    uart: &USART,
+    baud: u32,
+    clocks: &Clocks,
+) -> Result<(), config::InvalidConfig> {
+    let pclk_freq = USART::clock(clocks).raw();
+
+    let (over8, div) = if uart.irda_enabled() {
+        let div = (pclk_freq + (baud / 2)) / baud;
+        (false, div)
+    } else {
+        calculate_brr(pclk_freq, baud)?
+    };
+
+    // Divider must not be changed during a transfer
+    uart.bflush().ok();
+    uart.cr1().modify(|_, w| w.ue().clear_bit());
+    uart.brr().write(|w| unsafe { w.bits(div as u16) });
+    uart.update_irda_prescaler(pclk_freq);
+    uart.cr1().modify(|_, w| {
+        w.over8().bit(over8);
+        w.ue().set_bit()
+    });
+    Ok(())
 }
 
 fn calculate_brr(pclk_freq: u32, baud: u32) -> Result<(bool, u32), config::InvalidConfig> {
@@ -394,7 +459,13 @@ macro_rules! halUsart {
         pub type $Tx<WORD = u8> = Tx<$USART, WORD>;
         pub type $Rx<WORD = u8> = Rx<$USART, WORD>;
 
-        impl Instance for $USART {}
+        impl Instance for $USART {
+            #[cfg(feature = "async")]
+            fn wakers() -> &'static asynch::Wakers {
+                static WAKERS: asynch::Wakers = asynch::Wakers::new();
+                &WAKERS
+            }
+        }
     };
 }
 pub(crate) use halUsart;
@@ -413,7 +484,13 @@ macro_rules! halUart {
         pub type $Tx<WORD = u8> = Tx<$UART, WORD>;
         pub type $Rx<WORD = u8> = Rx<$UART, WORD>;
 
-        impl Instance for $UART {}
+        impl Instance for $UART {
+            #[cfg(feature = "async")]
+            fn wakers() -> &'static asynch::Wakers {
+                static WAKERS: asynch::Wakers = asynch::Wakers::new();
+                &WAKERS
+            }
+        }
     };
 }
 
@@ -432,34 +509,35 @@ halUart! { pac::UART10, Serial10, Rx10, Tx10 }
 
 impl<UART: CommonPins> Rx<UART, u8> {
     pub(crate) fn with_u16_data(self) -> Rx<UART, u16> {
-        Rx::new(self.usart, self.pin)
+        Rx::new(self.usart, self.pin, self.baud)
     }
 }
 
 impl<UART: CommonPins> Rx<UART, u16> {
     pub(crate) fn with_u8_data(self) -> Rx<UART, u8> {
-        Rx::new(self.usart, self.pin)
+        Rx::new(self.usart, self.pin, self.baud)
     }
 }
 
 impl<UART: CommonPins> Tx<UART, u8> {
     pub(crate) fn with_u16_data(self) -> Tx<UART, u16> {
-        Tx::new(self.usart, self.pin)
+        Tx::new(self.usart, self.pin, self.baud)
     }
 }
 
 impl<UART: CommonPins> Tx<UART, u16> {
     pub(crate) fn with_u8_data(self) -> Tx<UART, u8> {
-        Tx::new(self.usart, self.pin)
+        Tx::new(self.usart, self.pin, self.baud)
     }
 }
 
 impl<UART: CommonPins, WORD> Rx<UART, WORD> {
-    pub(crate) fn new(usart: UART, pin: UART::Rx<PushPull>) -> Self {
+    pub(crate) fn new(usart: UART, pin: UART::Rx<PushPull>, baud: u32) -> Self {
         Self {
             _word: PhantomData,
             usart,
             pin,
+            baud,
         }
     }
 
@@ -469,11 +547,12 @@ impl<UART: CommonPins, WORD> Rx<UART, WORD> {
 }
 
 impl<UART: CommonPins, WORD> Tx<UART, WORD> {
-    pub(crate) fn new(usart: UART, pin: UART::Tx<PushPull>) -> Self {
+    pub(crate) fn new(usart: UART, pin: UART::Tx<PushPull>, baud: u32) -> Self {
         Self {
             _word: PhantomData,
             usart,
             pin,
+            baud,
         }
     }
 
@@ -0,0 +1,339 @@
+//! [`embedded_io_async`] implementation transferring data with DMA
+//!
+//! Reads return as soon as the buffer is full or the IDLE line is detected after
+//! some data was received.
+//!
+//! Interrupts of the DMA streams and the USART must be unmasked in the NVIC
+//! and `on_interrupt` functions called from their handlers:
+//!
+//! ```ignore
+//! #[interrupt]
+//! fn USART1() {
+//!     Serial1::on_interrupt();
+//! }
+//!
+//! #[interrupt]
+//! fn DMA2_STREAM2() {
+//!     dma::Stream2::<pac::DMA2>::on_interrupt();
+//! }
+//! ```
+
+use core::future::poll_fn;
+use core::task::Poll;
+
+use atomic_waker::AtomicWaker;
+
+use super::{Error, Event, Flag, Instance, RegisterBlockImpl, Rx, Serial, Tx};
+use crate::dma::{
+    self,
+    traits::{Channel, DMASet, Stream},
+    ChannelX, DmaDataSize, DmaDirection, MemoryToPeripheral, PeripheralToMemory,
+};
+
+/// Maximum number of bytes in one DMA transfer
+const MAX_CHUNK: usize = u16::MAX as usize;
+
+#[doc(hidden)]
+pub struct Wakers {
+    rx: AtomicWaker,
+    tx: AtomicWaker,
+}
+
+impl Wakers {
+    pub const fn new() -> Self {
+        Self {
+            rx: AtomicWaker::new(),
+            tx: AtomicWaker::new(),
+        }
+    }
+}
+
+impl<USART: Instance> Serial<USART> {
+    /// Wakes the tasks waiting for IDLE line or transmission complete.
+    ///
+    /// Must be called from the `USARTx`/`UARTx` interrupt handler.
+    pub fn on_interrupt() {
+        let usart = unsafe { &*USART::ptr() };
+        // Mask interrupts until the woken tasks check the status flags
+        usart.listen_event(Some(Event::Idle | Event::TransmissionComplete), None);
+        USART::wakers().rx.wake();
+        USART::wakers().tx.wake();
+    }
+}
+
+/// Serial receiver reading data with DMA
+pub struct AsyncRx<USART: Instance, STREAM, const CHANNEL: u8> {
+    rx: Rx<USART>,
+    stream: STREAM,
+}
+
+/// Serial transmitter writing data with DMA
+pub struct AsyncTx<USART: Instance, STREAM, const CHANNEL: u8> {
+    tx: Tx<USART>,
+    stream: STREAM,
+}
+
+impl<USART: Instance> Rx<USART> {
+    /// Converts blocking [Rx] to async [AsyncRx] that uses `stream` to receive data
+    pub fn use_dma_async<STREAM, const CHANNEL: u8>(
+        self,
+        stream: STREAM,
+    ) -> AsyncRx<USART, STREAM, CHANNEL>
+    where
+        STREAM: Stream,
+        ChannelX<CHANNEL>: Channel,
+        Rx<USART>: DMASet<STREAM, CHANNEL, PeripheralToMemory>,
+    {
+        AsyncRx { rx: self, stream }
+    }
+}
+
+impl<USART: Instance> Tx<USART> {
+    /// Converts blocking [Tx] to async [AsyncTx] that uses `stream` to send data
+    pub fn use_dma_async<STREAM, const CHANNEL: u8>(
+        self,
+        stream: STREAM,
+    ) -> AsyncTx<USART, STREAM, CHANNEL>
+    where
+        STREAM: Stream,
+        ChannelX<CHANNEL>: Channel,
+        Tx<USART>: DMASet<STREAM, CHANNEL, MemoryToPeripheral>,
+    {
+        AsyncTx { tx: self, stream }
+    }
+}
+
+/// Stops the stream and DMA requests when transfer is finished or its future is dropped
+struct StopOnDrop<'a, USART: Instance, STREAM: Stream> {
+    usart: &'a USART,
+    stream: &'a mut STREAM,
+    rx: bool,
+}
+
+impl<USART: Instance, STREAM: Stream> Drop for StopOnDrop<'_, USART, STREAM> {
+    fn drop(&mut self) {
+        if self.rx {
+            self.usart.listen_event(Some(Event::Idle.into()), None);
+            self.usart.set_dma_rx(false);
+        } else {
+            self.usart.set_dma_tx(false);
+        }
+        dma::stop_stream(self.stream);
+    }
+}
+
+impl<USART: Instance, STREAM, const CHANNEL: u8> AsyncRx<USART, STREAM, CHANNEL>
+where
+    STREAM: Stream,
+    ChannelX<CHANNEL>: Channel,
+    Rx<USART>: DMASet<STREAM, CHANNEL, PeripheralToMemory>,
+{
+    /// Releases the receiver and DMA stream
+    pub fn release(self) -> (Rx<USART>, STREAM) {
+        (self.rx, self.stream)
+    }
+
+    /// Receives data into `buf`.
+    ///
+    /// Returns when `buf` is full or the line becomes idle after at least one byte was received.
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let len = buf.len().min(MAX_CHUNK);
+        let buf = &mut buf[..len];
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let usart = &self.rx.usart;
+
+        let guard = StopOnDrop {
+            usart,
+            stream: &mut self.stream,
+            rx: true,
+        };
+
+        // NOTE(unsafe) memory is borrowed until the stream is stopped by the guard
+        unsafe {
+            dma::start_oneshot(
+                guard.stream,
+                ChannelX::<CHANNEL>::VALUE,
+                DmaDirection::PeripheralToMemory,
+                USART::peri_address(),
+                (buf.as_mut_ptr() as u32, buf.len() as u16),
+                true,
+                DmaDataSize::Byte,
+            );
+        }
+        usart.set_dma_rx(true);
+
+        let result = poll_fn(|cx| {
+            USART::wakers().rx.register(cx.waker());
+            match dma::poll_stream(guard.stream, cx) {
+                Poll::Ready(Ok(())) => return Poll::Ready(Ok(buf.len())),
+                Poll::Ready(Err(_)) => return Poll::Ready(Err(Error::Dma)),
+                Poll::Pending => {}
+            }
+            let flags = usart.flags();
+            if flags
+                .intersects(Flag::Overrun | Flag::Noise | Flag::FramingError | Flag::ParityError)
+            {
+                // The byte in DR is part of the failed reception, it is dropped with the flags
+                dma::stop_stream(guard.stream);
+                clear_flags(usart);
+                return Poll::Ready(Err(if flags.contains(Flag::Overrun) {
+                    Error::Overrun
+                } else if flags.contains(Flag::Noise) {
+                    Error::Noise
+                } else if flags.contains(Flag::FramingError) {
+                    Error::FrameFormat
+                } else {
+                    Error::Parity
+                }));
+            }
+            if flags.contains(Flag::Idle) {
+                // Stop the stream before counting received bytes
+                dma::stop_stream(guard.stream);
+                let mut received = buf.len() - guard.stream.number_of_transfers() as usize;
+                // A byte received after the last DMA request is still in DR
+                if let Some(byte) = clear_flags(usart) {
+                    if received < buf.len() {
+                        buf[received] = byte;
+                        received += 1;
+                    }
+                }
+                if received > 0 {
+                    return Poll::Ready(Ok(received));
+                }
+                // IDLE flag was left from previous reception, restart
+                // NOTE(unsafe) memory is still borrowed by the guard
+                unsafe {
+                    dma::start_oneshot(
+                        guard.stream,
+                        ChannelX::<CHANNEL>::VALUE,
+                        DmaDirection::PeripheralToMemory,
+                        USART::peri_address(),
+                        (buf.as_mut_ptr() as u32, buf.len() as u16),
+                        true,
+                        DmaDataSize::Byte,
+                    );
+                }
+            }
+            usart.listen_event(None, Some(Event::Idle.into()));
+            Poll::Pending
+        })
+        .await;
+        drop(guard);
+
+        result
+    }
+}
+
+/// Clears the IDLE and error flags by reading SR then DR, the DMA stream must be stopped.
+///
+/// Returns the byte which was pending in DR.
+fn clear_flags<USART: Instance>(usart: &USART) -> Option<u8> {
+    let flags = usart.flags();
+    let dr = usart.read_dr() as u8;
+    flags.contains(Flag::RxNotEmpty).then_some(dr)
+}
+
+impl<USART: Instance, STREAM, const CHANNEL: u8> AsyncTx<USART, STREAM, CHANNEL>
+where
+    STREAM: Stream,
+    ChannelX<CHANNEL>: Channel,
+    Tx<USART>: DMASet<STREAM, CHANNEL, MemoryToPeripheral>,
+{
+    /// Releases the transmitter and DMA stream
+    pub fn release(self) -> (Tx<USART>, STREAM) {
+        (self.tx, self.stream)
+    }
+
+    /// Sends data from `buf`, returns when all bytes are written to the transmit data register
+    pub async fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        let buf = &buf[..buf.len().min(MAX_CHUNK)];
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let usart = &self.tx.usart;
+
+        let guard = StopOnDrop {
+            usart,
+            stream: &mut self.stream,
+            rx: false,
+        };
+
+        // NOTE(unsafe) memory is borrowed until the stream is stopped by the guard
+        unsafe {
+            dma::start_oneshot(
+                guard.stream,
+                ChannelX::<CHANNEL>::VALUE,
+                DmaDirection::MemoryToPeripheral,
+                USART::peri_address(),
+                (buf.as_ptr() as u32, buf.len() as u16),
+                true,
+                DmaDataSize::Byte,
+            );
+        }
+        usart.set_dma_tx(true);
+
+        poll_fn(|cx| dma::poll_stream(guard.stream, cx))
+            .await
+            .map_err(|_| Error::Dma)?;
+        drop(guard);
+
+        Ok(buf.len())
+    }
+
+    /// Waits until the last byte leaves the shift register
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        let usart = &self.tx.usart;
+        poll_fn(|cx| {
+            USART::wakers().tx.register(cx.waker());
+            if usart.flags().contains(Flag::TransmissionComplete) {
+                Poll::Ready(Ok(()))
+            } else {
+                usart.listen_event(None, Some(Event::TransmissionComplete.into()));
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+impl<USART: Instance, STREAM, const CHANNEL: u8> embedded_io::ErrorType
+    for AsyncRx<USART, STREAM, CHANNEL>
+{
+    type Error = Error;
+}
+
+impl<USART: Instance, STREAM, const CHANNEL: u8> embedded_io::ErrorType
+    for AsyncTx<USART, STREAM, CHANNEL>
+{
+    type Error = Error;
+}
+
+impl<USART: Instance, STREAM, const CHANNEL: u8> embedded_io_async::Read
+    for AsyncRx<USART, STREAM, CHANNEL>
+where
+    STREAM: Stream,
+    ChannelX<CHANNEL>: Channel,
+    Rx<USART>: DMASet<STREAM, CHANNEL, PeripheralToMemory>,
+{
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.read(buf).await
+    }
+}
+
+impl<USART: Instance, STREAM, const CHANNEL: u8> embedded_io_async::Write
+    for AsyncTx<USART, STREAM, CHANNEL>
+where
+    STREAM: Stream,
+    ChannelX<CHANNEL>: Channel,
+    Tx<USART>: DMASet<STREAM, CHANNEL, MemoryToPeripheral>,
+{
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.write(buf).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.flush().await
+    }
+}
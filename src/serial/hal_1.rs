@@ -9,7 +9,7 @@ mod nb {
                 Error::FrameFormat => ErrorKind::FrameFormat,
                 Error::Parity => ErrorKind::Parity,
                 Error::Noise => ErrorKind::Noise,
-                Error::Other => ErrorKind::Other,
+                Error::Other | Error::Dma => ErrorKind::Other,
             }
         }
     }
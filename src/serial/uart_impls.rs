@@ -4,6 +4,9 @@ use enumflags2::BitFlags;
 pub trait RegisterBlockImpl: UartExt {
     const IRDA: bool;
     fn configure_irda(&self, irda: IrdaMode, pclk_freq: u32);
+    fn irda_enabled(&self) -> bool;
+    /// Recomputes the IrDA low-power prescaler after the bus clock changed
+    fn update_irda_prescaler(&self, pclk_freq: u32);
     fn set_stopbits(&self, bits: config::StopBits);
 
     fn read_u16(&self) -> nb::Result<u16, Error> {
@@ -127,6 +130,10 @@ pub trait RegisterBlockImpl: UartExt {
         self.sr()
             .write(|w| unsafe { w.bits(0xffff & !flags.bits()) });
     }
+    #[inline(always)]
+    fn read_dr(&self) -> u16 {
+        self.dr().read().dr().bits()
+    }
     fn clear_idle_interrupt(&self) {
         let _ = self.sr().read();
         let _ = self.dr().read();
@@ -218,6 +225,16 @@ pub trait RegisterBlockImpl: UartExt {
             DmaConfig::None => {}
         }
     }
+
+    /// Enables or disables DMA requests on reception, keeping the transmitter setting
+    fn set_dma_rx(&self, enable: bool) {
+        self.cr3().modify(|_, w| w.dmar().bit(enable));
+    }
+
+    /// Enables or disables DMA requests on transmission, keeping the receiver setting
+    fn set_dma_tx(&self, enable: bool) {
+        self.cr3().modify(|_, w| w.dmat().bit(enable));
+    }
 }
 
 impl RegisterBlockImpl for crate::pac::usart1::RegisterBlock {
@@ -245,13 +262,27 @@ impl RegisterBlockImpl for crate::pac::usart1::RegisterBlock {
             IrdaMode::LowPower => unsafe {
                 self.gtpr().reset();
                 self.cr3().write(|w| w.iren().enabled().irlp().low_power());
-                // FIXME
                 self.gtpr()
-                    .write(|w| w.psc().bits((1843200u32 / pclk_freq) as u8));
+                    .write(|w| w.psc().bits(irda_low_power_psc(pclk_freq)));
             },
             IrdaMode::None => {}
         }
     }
+    fn irda_enabled(&self) -> bool {
+        self.cr3().read().iren().bit_is_set()
+    }
+    fn update_irda_prescaler(&self, pclk_freq: u32) {
+        let cr3 = self.cr3().read();
+        if cr3.iren().bit_is_set() && cr3.irlp().is_low_power() {
+            self.gtpr()
+                .modify(|_, w| unsafe { w.psc().bits(irda_low_power_psc(pclk_freq)) });
+        }
+    }
+}
+
+// FIXME
+fn irda_low_power_psc(pclk_freq: u32) -> u8 {
+    (1843200u32 / pclk_freq) as u8
 }
 
 #[cfg(feature = "uart4")]
@@ -271,4 +302,8 @@ impl RegisterBlockImpl for crate::pac::uart4::RegisterBlock {
         });
     }
     fn configure_irda(&self, _irda: IrdaMode, _pclk_freq: u32) {}
+    fn irda_enabled(&self) -> bool {
+        false
+    }
+    fn update_irda_prescaler(&self, _pclk_freq: u32) {}
 }
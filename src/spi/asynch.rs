@@ -0,0 +1,256 @@
+//! [`embedded_hal_async::spi::SpiBus`] implementation using a couple of DMA streams
+//!
+//! Interrupts of both streams must be unmasked in the NVIC and
+//! [`StreamX::on_interrupt`](crate::dma::StreamX::on_interrupt) called from their handlers.
+
+use core::future::poll_fn;
+use core::task::Poll;
+
+use super::{Error, FrameSize, Instance, Rx, Spi, Tx};
+use crate::dma::{
+    self,
+    traits::{Channel, DMASet, Stream},
+    ChannelX, DmaDataSize, DmaDirection, MemoryToPeripheral, PeripheralToMemory,
+};
+
+/// Maximum number of frames in one DMA transfer
+const MAX_CHUNK: usize = u16::MAX as usize;
+
+/// Full-duplex SPI master transferring data with DMA
+pub struct SpiBusDma<SPI: Instance, TXS, const TXC: u8, RXS, const RXC: u8, W = u8> {
+    spi: Spi<SPI, false, W>,
+    tx_stream: TXS,
+    rx_stream: RXS,
+    /// Source of sent frames on read and destination of received frames on write
+    dummy: W,
+}
+
+impl<SPI: Instance, W: FrameSize> Spi<SPI, false, W> {
+    /// Converts blocking [Spi] to async [SpiBusDma] that uses `tx_stream` and `rx_stream` to send/receive data
+    pub fn use_dma_bus<TXS, const TXC: u8, RXS, const RXC: u8>(
+        self,
+        tx_stream: TXS,
+        rx_stream: RXS,
+    ) -> SpiBusDma<SPI, TXS, TXC, RXS, RXC, W>
+    where
+        TXS: Stream,
+        ChannelX<TXC>: Channel,
+        Tx<SPI>: DMASet<TXS, TXC, MemoryToPeripheral>,
+        RXS: Stream,
+        ChannelX<RXC>: Channel,
+        Rx<SPI>: DMASet<RXS, RXC, PeripheralToMemory>,
+    {
+        SpiBusDma {
+            spi: self,
+            tx_stream,
+            rx_stream,
+            dummy: W::default(),
+        }
+    }
+}
+
+/// Stops the streams when transfer is finished or its future is dropped
+struct StopOnDrop<'a, SPI: Instance, TXS: Stream, RXS: Stream> {
+    spi: &'a SPI,
+    tx_stream: &'a mut TXS,
+    rx_stream: &'a mut RXS,
+}
+
+impl<SPI: Instance, TXS: Stream, RXS: Stream> Drop for StopOnDrop<'_, SPI, TXS, RXS> {
+    fn drop(&mut self) {
+        self.spi.cr2().modify(|_, w| {
+            w.txdmaen().clear_bit();
+            w.rxdmaen().clear_bit()
+        });
+        dma::stop_stream(self.tx_stream);
+        dma::stop_stream(self.rx_stream);
+    }
+}
+
+fn mem<W>(ptr: *const W, len: usize) -> (u32, u16) {
+    (ptr as u32, len as u16)
+}
+
+impl<SPI, TXS, const TXC: u8, RXS, const RXC: u8, W> SpiBusDma<SPI, TXS, TXC, RXS, RXC, W>
+where
+    SPI: Instance,
+    W: FrameSize,
+    TXS: Stream,
+    ChannelX<TXC>: Channel,
+    Tx<SPI>: DMASet<TXS, TXC, MemoryToPeripheral>,
+    RXS: Stream,
+    ChannelX<RXC>: Channel,
+    Rx<SPI>: DMASet<RXS, RXC, PeripheralToMemory>,
+{
+    /// Releases the SPI and DMA streams
+    pub fn release(self) -> (Spi<SPI, false, W>, TXS, RXS) {
+        (self.spi, self.tx_stream, self.rx_stream)
+    }
+
+    /// Transfers up to [`MAX_CHUNK`] frames, `rx` and `tx` are `(address, len)` of memory
+    async fn transfer_chunk(
+        &mut self,
+        rx: (u32, u16),
+        rx_increment: bool,
+        tx: (u32, u16),
+        tx_increment: bool,
+    ) -> Result<(), Error> {
+        let size = if W::DFF {
+            DmaDataSize::HalfWord
+        } else {
+            DmaDataSize::Byte
+        };
+        let spi = &self.spi.inner.spi;
+        let dr = spi.dr().as_ptr() as u32;
+
+        // Drop stale data, clears OVR
+        while spi.sr().read().rxne().bit_is_set() {
+            let _ = spi.dr().read();
+        }
+
+        let guard = StopOnDrop {
+            spi,
+            tx_stream: &mut self.tx_stream,
+            rx_stream: &mut self.rx_stream,
+        };
+
+        // NOTE(unsafe) memory is borrowed until the streams are stopped by the guard
+        unsafe {
+            // RX requests are enabled first not to miss the first frame
+            spi.cr2().modify(|_, w| w.rxdmaen().set_bit());
+            dma::start_oneshot(
+                guard.rx_stream,
+                ChannelX::<RXC>::VALUE,
+                DmaDirection::PeripheralToMemory,
+                dr,
+                rx,
+                rx_increment,
+                size,
+            );
+            dma::start_oneshot(
+                guard.tx_stream,
+                ChannelX::<TXC>::VALUE,
+                DmaDirection::MemoryToPeripheral,
+                dr,
+                tx,
+                tx_increment,
+                size,
+            );
+            spi.cr2().modify(|_, w| w.txdmaen().set_bit());
+        }
+
+        poll_fn(|cx| {
+            let tx = dma::poll_stream(guard.tx_stream, cx);
+            let rx = dma::poll_stream(guard.rx_stream, cx);
+            match (tx, rx) {
+                (Poll::Ready(Err(_)), _) | (_, Poll::Ready(Err(_))) => Poll::Ready(Err(Error::Dma)),
+                (Poll::Ready(Ok(())), Poll::Ready(Ok(()))) => Poll::Ready(Ok(())),
+                _ => Poll::Pending,
+            }
+        })
+        .await?;
+        drop(guard);
+
+        let sr = spi.sr().read();
+        if sr.ovr().bit_is_set() {
+            // Read from the DR and SR to clear the OVR bit
+            let _ = spi.dr().read();
+            let _ = spi.sr().read();
+            Err(Error::Overrun)
+        } else if sr.modf().bit_is_set() {
+            // Write to CR1 to clear MODF
+            spi.cr1().modify(|_r, w| w);
+            Err(Error::ModeFault)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Sends `W::default()` while receiving data into `words`
+    pub async fn read(&mut self, words: &mut [W]) -> Result<(), Error> {
+        self.dummy = W::default();
+        let dummy = mem(&self.dummy, 1).0;
+        for chunk in words.chunks_mut(MAX_CHUNK) {
+            let rx = mem(chunk.as_mut_ptr(), chunk.len());
+            self.transfer_chunk(rx, true, (dummy, rx.1), false).await?;
+        }
+        Ok(())
+    }
+
+    /// Sends `words`, received data is discarded
+    pub async fn write(&mut self, words: &[W]) -> Result<(), Error> {
+        let dummy = mem(&self.dummy, 1).0;
+        for chunk in words.chunks(MAX_CHUNK) {
+            let tx = mem(chunk.as_ptr(), chunk.len());
+            self.transfer_chunk((dummy, tx.1), false, tx, true).await?;
+        }
+        Ok(())
+    }
+
+    /// Sends `write` while receiving data into `read`.
+    ///
+    /// If lengths differ, the shorter buffer is padded with `W::default()` frames
+    /// on send and extra received frames are discarded.
+    pub async fn transfer(&mut self, read: &mut [W], write: &[W]) -> Result<(), Error> {
+        let n = read.len().min(write.len());
+        let (read, read_rest) = read.split_at_mut(n);
+        let (write, write_rest) = write.split_at(n);
+        for (r, w) in read.chunks_mut(MAX_CHUNK).zip(write.chunks(MAX_CHUNK)) {
+            let rx = mem(r.as_mut_ptr(), r.len());
+            let tx = mem(w.as_ptr(), w.len());
+            self.transfer_chunk(rx, true, tx, true).await?;
+        }
+        self.read(read_rest).await?;
+        self.write(write_rest).await
+    }
+
+    /// Sends `words` replacing them with received data
+    pub async fn transfer_in_place(&mut self, words: &mut [W]) -> Result<(), Error> {
+        for chunk in words.chunks_mut(MAX_CHUNK) {
+            // Each frame is sent by TX stream before it is overwritten by RX stream
+            let buf = mem(chunk.as_mut_ptr(), chunk.len());
+            self.transfer_chunk(buf, true, buf, true).await?;
+        }
+        Ok(())
+    }
+}
+
+impl<SPI: Instance, TXS, const TXC: u8, RXS, const RXC: u8, W> embedded_hal::spi::ErrorType
+    for SpiBusDma<SPI, TXS, TXC, RXS, RXC, W>
+{
+    type Error = Error;
+}
+
+impl<SPI, TXS, const TXC: u8, RXS, const RXC: u8, W> embedded_hal_async::spi::SpiBus<W>
+    for SpiBusDma<SPI, TXS, TXC, RXS, RXC, W>
+where
+    SPI: Instance,
+    W: FrameSize + 'static,
+    TXS: Stream,
+    ChannelX<TXC>: Channel,
+    Tx<SPI>: DMASet<TXS, TXC, MemoryToPeripheral>,
+    RXS: Stream,
+    ChannelX<RXC>: Channel,
+    Rx<SPI>: DMASet<RXS, RXC, PeripheralToMemory>,
+{
+    async fn read(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        self.read(words).await
+    }
+
+    async fn write(&mut self, words: &[W]) -> Result<(), Self::Error> {
+        self.write(words).await
+    }
+
+    async fn transfer(&mut self, read: &mut [W], write: &[W]) -> Result<(), Self::Error> {
+        self.transfer(read, write).await
+    }
+
+    async fn transfer_in_place(&mut self, words: &mut [W]) -> Result<(), Self::Error> {
+        self.transfer_in_place(words).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        // Transfers are finished when receiving of the last frame is complete
+        Ok(())
+    }
+}
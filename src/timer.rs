@@ -46,6 +46,22 @@ pub mod monotonics;
     feature = "rtic-tim5"
 ))]
 pub use monotonics::*;
+#[cfg(feature = "embassy-time")]
+#[cfg(any(
+    feature = "embassy-tim2",
+    feature = "embassy-tim3",
+    feature = "embassy-tim4",
+    feature = "embassy-tim5"
+))]
+pub mod time_driver;
+#[cfg(feature = "embassy-time")]
+#[cfg(any(
+    feature = "embassy-tim2",
+    feature = "embassy-tim3",
+    feature = "embassy-tim4",
+    feature = "embassy-tim5"
+))]
+pub use time_driver::EmbassyTimeExt;
 
 mod hal_02;
 mod hal_1;
@@ -1,4 +1,5 @@
 use super::{compute_arr_presc, Error, FTimer, Flag, Instance, SysEvent, Timer};
+use crate::rcc::Clocks;
 use core::ops::{Deref, DerefMut};
 use cortex_m::peripheral::SYST;
 use fugit::{HertzU32 as Hertz, TimerDurationU32, TimerInstantU32};
@@ -67,6 +68,22 @@ impl<TIM: Instance> CounterHz<TIM> {
         self.tim.enable_counter(false);
         Ok(())
     }
+
+    /// Updates the timer clock from `clocks` returned by
+    /// [`CFGR::refreeze`](crate::rcc::CFGR::refreeze).
+    ///
+    /// A running counter is restarted with the same frequency.
+    pub fn configure(&mut self, clocks: &Clocks) -> Result<(), Error> {
+        let psc = u32::from(self.tim.read_prescaler());
+        let arr = TIM::read_auto_reload();
+        let timeout = self.clk / ((psc + 1) * (arr + 1));
+
+        self.0.configure(clocks);
+        if self.tim.is_counter_enabled() {
+            self.start(timeout)?;
+        }
+        Ok(())
+    }
 }
 
 /// Periodic non-blocking timer that implements [embedded_hal_02::timer::CountDown]
@@ -459,6 +459,23 @@ where
         self.tim.set_auto_reload(arr).unwrap();
         self.tim.cnt_reset();
     }
+
+    /// Updates the timer clock from `clocks` returned by
+    /// [`CFGR::refreeze`](crate::rcc::CFGR::refreeze), keeping the PWM frequency and duty cycles
+    pub fn configure(&mut self, clocks: &Clocks) {
+        let period = self.get_period();
+        let max_duty = TIM::read_auto_reload() + 1;
+
+        self.timer.configure(clocks);
+        self.set_period(period);
+
+        let new_max_duty = TIM::read_auto_reload() + 1;
+        for c in 0..TIM::CH_NUMBER {
+            let duty =
+                u64::from(TIM::read_cc_value(c)) * u64::from(new_max_duty) / u64::from(max_duty);
+            TIM::set_cc_value(c, duty as u32);
+        }
+    }
 }
 
 macro_rules! impl_advanced {
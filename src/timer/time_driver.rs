@@ -0,0 +1,287 @@
+//! [embassy-time](https://crates.io/crates/embassy-time) driver
+//!
+//! The timer is selected with one of `embassy-tim2`..`embassy-tim5` features
+//! and must be started before `embassy_time` is used:
+//!
+//! ```ignore
+//! dp.TIM2.embassy_time(&clocks);
+//! ```
+//!
+//! The driver takes the timer interrupt vector. Timer counts with `embassy_time_driver::TICK_HZ`
+//! frequency, 16-bit counters are extended to 64 bits by tracking overflows.
+//! Compare channel 1 marks the half of the counter period, channels 2-4 are used as alarms.
+
+use core::cell::Cell;
+use core::marker::PhantomData;
+use core::sync::atomic::{compiler_fence, AtomicU32, AtomicU8, Ordering};
+
+use cortex_m::interrupt::{CriticalSection, Mutex};
+use embassy_time_driver::{AlarmHandle, Driver, TICK_HZ};
+use enumflags2::BitFlags;
+
+use super::{Event, FTimer, Flag, Instance, WithPwm};
+use crate::{pac, rcc::Clocks};
+
+#[cfg(any(
+    all(feature = "embassy-tim2", feature = "embassy-tim3"),
+    all(feature = "embassy-tim2", feature = "embassy-tim4"),
+    all(feature = "embassy-tim2", feature = "embassy-tim5"),
+    all(feature = "embassy-tim3", feature = "embassy-tim4"),
+    all(feature = "embassy-tim3", feature = "embassy-tim5"),
+    all(feature = "embassy-tim4", feature = "embassy-tim5"),
+))]
+compile_error!("Only one `embassy-timX` feature can be enabled");
+
+#[cfg(all(feature = "embassy-tim2", feature = "rtic-tim2"))]
+compile_error!("`embassy-tim2` and `rtic-tim2` both take the TIM2 interrupt");
+
+#[cfg(all(feature = "embassy-tim3", feature = "rtic-tim3"))]
+compile_error!("`embassy-tim3` and `rtic-tim3` both take the TIM3 interrupt");
+
+#[cfg(all(feature = "embassy-tim4", feature = "rtic-tim4"))]
+compile_error!("`embassy-tim4` and `rtic-tim4` both take the TIM4 interrupt");
+
+#[cfg(all(feature = "embassy-tim5", feature = "rtic-tim5"))]
+compile_error!("`embassy-tim5` and `rtic-tim5` both take the TIM5 interrupt");
+
+const ALARM_COUNT: usize = 3;
+
+/// Alarm callback and its context pointer
+type Callback = (fn(*mut ()), *mut ());
+
+struct AlarmState {
+    timestamp: Cell<u64>,
+    callback: Cell<Option<Callback>>,
+}
+
+// NOTE(unsafe) context pointer is only passed back to the callback
+unsafe impl Send for AlarmState {}
+
+impl AlarmState {
+    const fn new() -> Self {
+        Self {
+            timestamp: Cell::new(u64::MAX),
+            callback: Cell::new(None),
+        }
+    }
+}
+
+const fn alarm_event(n: usize) -> Event {
+    match n {
+        0 => Event::C2,
+        1 => Event::C3,
+        _ => Event::C4,
+    }
+}
+
+const fn alarm_flag(n: usize) -> Flag {
+    match n {
+        0 => Flag::C2,
+        1 => Flag::C3,
+        _ => Flag::C4,
+    }
+}
+
+pub struct TimeDriver<TIM> {
+    /// Number of half periods of the counter elapsed
+    period: AtomicU32,
+    alarm_count: AtomicU8,
+    alarms: Mutex<[AlarmState; ALARM_COUNT]>,
+    _tim: PhantomData<fn() -> TIM>,
+}
+
+impl<TIM> TimeDriver<TIM> {
+    const fn new() -> Self {
+        Self {
+            period: AtomicU32::new(0),
+            alarm_count: AtomicU8::new(0),
+            alarms: Mutex::new([AlarmState::new(), AlarmState::new(), AlarmState::new()]),
+            _tim: PhantomData,
+        }
+    }
+}
+
+impl<TIM: Instance + WithPwm + crate::Steal + 'static> TimeDriver<TIM> {
+    #[inline(always)]
+    fn tim() -> TIM {
+        // NOTE(unsafe) timer is owned by the driver after start
+        unsafe { TIM::steal() }
+    }
+
+    #[inline(always)]
+    fn half_period() -> u64 {
+        (TIM::max_auto_reload() as u64 + 1) / 2
+    }
+
+    fn start(&self, tim: TIM, clocks: &Clocks) {
+        let mut tim = FTimer::<TIM, { TICK_HZ as u32 }>::new(tim, clocks).release();
+        unsafe {
+            tim.set_auto_reload_unchecked(TIM::max_auto_reload());
+        }
+        TIM::set_cc_value(0, Self::half_period() as u32);
+
+        // Load the prescaler without raising update interrupt
+        tim.trigger_update();
+        tim.clear_interrupt_flag(BitFlags::ALL);
+
+        self.period.store(0, Ordering::Relaxed);
+        tim.listen_event(Some(BitFlags::ALL), Some(Event::Update | Event::C1));
+        tim.enable_counter(true);
+    }
+
+    fn next_period(&self, cs: &CriticalSection) {
+        let period = self.period.load(Ordering::Relaxed) + 1;
+        self.period.store(period, Ordering::Relaxed);
+        let t = period as u64 * Self::half_period();
+
+        // Enable alarms which will fire before the next half period ends
+        let mut tim = Self::tim();
+        for (n, alarm) in self.alarms.borrow(cs).iter().enumerate() {
+            if alarm.timestamp.get() < t + Self::half_period() * 3 / 2 {
+                tim.listen_event(None, Some(alarm_event(n).into()));
+            }
+        }
+    }
+
+    fn trigger_alarm(&self, n: usize, cs: &CriticalSection) {
+        let alarm = &self.alarms.borrow(cs)[n];
+        // Compare match of a later period
+        if alarm.timestamp.get() > self.now() {
+            return;
+        }
+
+        Self::tim().listen_event(Some(alarm_event(n).into()), None);
+        alarm.timestamp.set(u64::MAX);
+        if let Some((callback, ctx)) = alarm.callback.get() {
+            callback(ctx);
+        }
+    }
+
+    fn on_interrupt(&self) {
+        let mut tim = Self::tim();
+        let flags = tim.get_interrupt_flag();
+        tim.clear_interrupt_flag(flags);
+
+        cortex_m::interrupt::free(|cs| {
+            if flags.contains(Flag::Update) {
+                self.next_period(cs);
+            }
+            if flags.contains(Flag::C1) {
+                self.next_period(cs);
+            }
+            for n in 0..ALARM_COUNT {
+                if flags.contains(alarm_flag(n)) {
+                    self.trigger_alarm(n, cs);
+                }
+            }
+        });
+    }
+}
+
+impl<TIM: Instance + WithPwm + crate::Steal + 'static> Driver for TimeDriver<TIM> {
+    fn now(&self) -> u64 {
+        let period = self.period.load(Ordering::Relaxed);
+        compiler_fence(Ordering::Acquire);
+        let counter: u32 = Self::tim().read_count().into();
+        // Counter value is in the first half of its period when `period` is even
+        // and in the second one when it's odd, even if the overflow is not handled yet
+        let half = Self::half_period();
+        period as u64 * half + (counter as u64 ^ ((period & 1) as u64 * half))
+    }
+
+    unsafe fn allocate_alarm(&self) -> Option<AlarmHandle> {
+        self.alarm_count
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < ALARM_COUNT as u8).then_some(n + 1)
+            })
+            .ok()
+            .map(|n| AlarmHandle::new(n))
+    }
+
+    fn set_alarm_callback(&self, alarm: AlarmHandle, callback: fn(*mut ()), ctx: *mut ()) {
+        cortex_m::interrupt::free(|cs| {
+            self.alarms.borrow(cs)[alarm.id() as usize]
+                .callback
+                .set(Some((callback, ctx)));
+        });
+    }
+
+    fn set_alarm(&self, alarm: AlarmHandle, timestamp: u64) -> bool {
+        let n = alarm.id() as usize;
+        cortex_m::interrupt::free(|cs| {
+            let state = &self.alarms.borrow(cs)[n];
+            let mut tim = Self::tim();
+            let event = alarm_event(n);
+
+            state.timestamp.set(timestamp);
+            let t = self.now();
+            if timestamp <= t {
+                // Alarm is in the past, caller handles it
+                tim.listen_event(Some(event.into()), None);
+                state.timestamp.set(u64::MAX);
+                return false;
+            }
+
+            TIM::set_cc_value(
+                n as u8 + 1,
+                (timestamp & TIM::max_auto_reload() as u64) as u32,
+            );
+            tim.clear_interrupt_flag(alarm_flag(n).into());
+
+            // Farther alarms are enabled when the period is updated
+            if timestamp - t < Self::half_period() * 3 / 2 {
+                tim.listen_event(None, Some(event.into()));
+            } else {
+                tim.listen_event(Some(event.into()), None);
+            }
+
+            // Timestamp could have passed while the compare value was written
+            if timestamp <= self.now() {
+                tim.listen_event(Some(event.into()), None);
+                state.timestamp.set(u64::MAX);
+                return false;
+            }
+
+            true
+        })
+    }
+}
+
+pub trait EmbassyTimeExt: Sized {
+    /// Starts [embassy-time](https://crates.io/crates/embassy-time) driver on this timer
+    /// and unmasks its interrupt in the NVIC.
+    fn embassy_time(self, clocks: &Clocks);
+}
+
+macro_rules! time_driver {
+    ($TIM:ident) => {
+        embassy_time_driver::time_driver_impl!(static DRIVER: TimeDriver<pac::$TIM> = TimeDriver::new());
+
+        #[no_mangle]
+        #[allow(non_snake_case)]
+        unsafe extern "C" fn $TIM() {
+            DRIVER.on_interrupt();
+        }
+
+        impl EmbassyTimeExt for pac::$TIM {
+            fn embassy_time(self, clocks: &Clocks) {
+                DRIVER.start(self, clocks);
+                unsafe {
+                    cortex_m::peripheral::NVIC::unmask(pac::Interrupt::$TIM);
+                }
+            }
+        }
+    };
+}
+
+#[cfg(all(feature = "tim2", feature = "embassy-tim2"))]
+time_driver!(TIM2);
+
+#[cfg(all(feature = "tim3", feature = "embassy-tim3"))]
+time_driver!(TIM3);
+
+#[cfg(all(feature = "tim4", feature = "embassy-tim4"))]
+time_driver!(TIM4);
+
+#[cfg(all(feature = "tim5", feature = "embassy-tim5"))]
+time_driver!(TIM5);